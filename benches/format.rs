@@ -0,0 +1,78 @@
+//! 目录树组装与渲染的性能测试
+//!
+//! 使用合成的条目列表（最多约100万个条目）验证渲染耗时随条目数线性增长。
+//! 运行：`cargo bench --bench format`
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::io;
+use std::path::{Path, PathBuf};
+use treecmd::{Config, Entry, FileKind, FileMeta, Formatter, TreeNode};
+
+/// 按深度优先顺序生成合成条目：每个目录`fanout`个子条目，最后一层为文件
+fn synthetic_entries(fanout: usize, levels: usize) -> Vec<Entry> {
+    fn fill(out: &mut Vec<Entry>, parent: &Path, depth: usize, fanout: usize, levels: usize) {
+        for i in 0..fanout {
+            if depth < levels {
+                let path = parent.join(format!("dir{i}"));
+                out.push(Entry::new(path.clone(), depth, FileKind::Dir));
+                fill(out, &path, depth + 1, fanout, levels);
+            } else {
+                let path = parent.join(format!("file{i}.txt"));
+                let meta = FileMeta {
+                    size: i as u64,
+                    ..FileMeta::default()
+                };
+                out.push(Entry::new(path, depth, FileKind::File).with_metadata(meta));
+            }
+        }
+    }
+
+    let root = PathBuf::from("root");
+    let mut entries = vec![Entry::new(root.clone(), 0, FileKind::Dir)];
+    fill(&mut entries, &root, 1, fanout, levels);
+    entries
+}
+
+fn bench_format(c: &mut Criterion) {
+    let config = Config::builder().no_color(true).build().unwrap();
+    let mut formatter = Formatter::new(config);
+
+    let mut group = c.benchmark_group("format");
+    group.sample_size(10);
+
+    // 约1万、10万、100万个条目
+    for (fanout, levels) in [(100, 2), (46, 3), (100, 3)] {
+        let entries = synthetic_entries(fanout, levels);
+        let size = entries.len();
+        group.throughput(Throughput::Elements(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("tree", size), &entries, |b, entries| {
+            b.iter_batched(
+                || entries.clone(),
+                |entries| {
+                    let tree = TreeNode::from_entries(entries).unwrap();
+                    formatter.format_tree(&mut io::sink(), &tree).unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(BenchmarkId::new("json", size), &entries, |b, entries| {
+            b.iter_batched(
+                || entries.clone(),
+                |entries| {
+                    let tree = TreeNode::from_entries(entries).unwrap();
+                    formatter
+                        .format_json(&mut io::sink(), Some(&tree), &[])
+                        .unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_format);
+criterion_main!(benches);
@@ -0,0 +1,69 @@
+//! 串行遍历与并行遍历的性能对比
+//!
+//! 运行：`cargo bench --bench walk`
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::fs;
+use std::path::Path;
+use treecmd::{Config, Walker};
+
+/// 构建合成目录树：`width`个子目录，每层`width`个子目录，共`levels`层，每个目录`files`个文件
+fn build_tree(dir: &Path, width: usize, levels: usize, files: usize) {
+    for i in 0..files {
+        fs::write(dir.join(format!("file{i}.txt")), b"x").unwrap();
+    }
+    if levels == 0 {
+        return;
+    }
+    for i in 0..width {
+        let sub = dir.join(format!("dir{i}"));
+        fs::create_dir(&sub).unwrap();
+        build_tree(&sub, width, levels - 1, files);
+    }
+}
+
+fn config_for(path: &Path, threads: Option<usize>) -> Config {
+    let mut builder = Config::builder()
+        .all(true)
+        .no_color(true)
+        .level(16)
+        .path(path);
+    if let Some(threads) = threads {
+        builder = builder.threads(threads);
+    }
+    builder.build().unwrap()
+}
+
+fn bench_walk(c: &mut Criterion) {
+    let dir = tempfile::Builder::new()
+        .prefix("treecmd-bench")
+        .tempdir()
+        .unwrap();
+    // 1 + 8 + 64 + 512 个目录，每个目录20个文件，约1.2万个条目
+    build_tree(dir.path(), 8, 3, 20);
+
+    let mut group = c.benchmark_group("walk");
+    group.sample_size(20);
+
+    let serial = Walker::new(config_for(dir.path(), None));
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            let entries: Vec<_> = serial.walk().collect();
+            serial.sort_entries(entries)
+        })
+    });
+
+    for threads in [1, 2, 4, 8] {
+        let parallel = Walker::new(config_for(dir.path(), Some(threads)));
+        group.bench_with_input(
+            BenchmarkId::new("parallel", threads),
+            &parallel,
+            |b, walker| b.iter(|| walker.walk_parallel()),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_walk);
+criterion_main!(benches);
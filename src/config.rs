@@ -1,11 +1,23 @@
+use crate::entry::FileMeta;
+use crate::pattern::Pattern;
 use atty;
-use clap::Parser;
+use chrono::format::{Item, StrftimeItems};
+use clap::{Arg, ArgAction, Parser, ValueEnum};
+use std::ffi::OsString;
+use std::fmt;
 use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// `--timefmt`中表示相对时间的取值
+pub const RELATIVE_TIMEFMT: &str = "relative";
 
 /// 以树状结构递归显示目录内容的命令行工具
 /// 兼容Linux tree命令参数
-#[derive(Parser, Debug, Clone)]
+#[derive(Parser, Debug, Clone, Default)]
 #[command(author, version, about, long_about = None)]
+// -h与GNU tree一致表示人性化大小，帮助只保留长选项--help
+#[command(disable_help_flag = true)]
+#[command(arg(Arg::new("help").long("help").action(ArgAction::Help).help("Print help")))]
 pub struct Args {
     /// Show all files and directories, including hidden ones
     #[arg(short = 'a', long = "all")]
@@ -27,9 +39,36 @@ pub struct Args {
     #[arg(short = 'D', long = "mtime")]
     pub mtime: bool,
 
-    /// Append '/' to directories, '*' to executables, '@' to symbolic links
-    #[arg(short = 'F', long = "filelimit")]
-    pub filelimit: bool,
+    /// Format dates with the strftime FORMAT, or "relative" for times like "3 days ago" (implies -D)
+    #[arg(long = "timefmt", value_name = "FORMAT")]
+    pub timefmt: Option<String>,
+
+    /// Which timestamp -D shows (implies -D)
+    #[arg(long = "time", value_name = "WORD", value_enum)]
+    pub time: Option<TimeField>,
+
+    /// Show dates in UTC instead of the local time zone
+    #[arg(long = "utc")]
+    pub utc: bool,
+
+    /// Append '/' to directories, '*' to executables, '@' to symbolic links, '=' to sockets and '|' to FIFOs
+    #[arg(short = 'F', long = "classify")]
+    pub classify: bool,
+
+    /// Report each directory's size as the total size of everything below it (implies -s)
+    #[arg(
+        long = "du",
+        value_name = "MODE",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "apparent"
+    )]
+    pub du: Option<DuMode>,
+
+    /// Do not descend into directories with more than N entries
+    #[arg(long = "filelimit", value_name = "N")]
+    pub filelimit: Option<usize>,
 
     /// Print full path prefix for each file
     #[arg(short = 'f', long = "full-path")]
@@ -43,9 +82,9 @@ pub struct Args {
     #[arg(short = 'i', long = "noreport")]
     pub noreport: bool,
 
-    /// Exclude files and directories matching the given pattern
-    #[arg(short = 'I', long = "exclude")]
-    pub exclude: Option<String>,
+    /// Exclude files and directories matching the glob PATTERN; use '|' to separate alternatives (repeatable)
+    #[arg(short = 'I', long = "exclude", value_name = "PATTERN")]
+    pub exclude: Vec<String>,
 
     /// Follow symbolic links as if they were directories
     #[arg(short = 'l', long = "follow-links")]
@@ -59,9 +98,21 @@ pub struct Args {
     #[arg(short = 'N', long = "literal")]
     pub literal: bool,
 
-    /// Show only files and directories matching the given pattern
-    #[arg(short = 'P', long = "include")]
-    pub include: Option<String>,
+    /// Show only files and directories matching the glob PATTERN; use '|' to separate alternatives (repeatable)
+    #[arg(short = 'P', long = "include", value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    /// Ignore case when sorting names and when matching -I and -P patterns
+    #[arg(long = "ignore-case")]
+    pub ignore_case: bool,
+
+    /// Treat -I and -P patterns as regular expressions instead of globs
+    #[arg(long = "regex")]
+    pub regex: bool,
+
+    /// Match -I and -P patterns against the path relative to the starting directory instead of the name
+    #[arg(long = "match-path")]
+    pub match_path: bool,
 
     /// Show file permissions
     #[arg(short = 'p', long = "perms")]
@@ -75,9 +126,33 @@ pub struct Args {
     #[arg(short = 's', long = "size")]
     pub size: bool,
 
-    /// Sort files by modification time, newest first
-    #[arg(short = 't', long = "sort")]
-    pub sort: Option<String>,
+    /// Print sizes in a more human readable way, using powers of 1024 (K, M, G, T, P)
+    #[arg(short = 'h', long = "human")]
+    pub human: bool,
+
+    /// Like -h, but use powers of 1000 instead of 1024
+    #[arg(long = "si")]
+    pub si: bool,
+
+    /// Sort entries by KEY (default: name)
+    #[arg(short = 't', long = "sort", value_name = "KEY", value_enum)]
+    pub sort: Option<SortKey>,
+
+    /// Reverse the order of the sort
+    #[arg(short = 'r', long = "reverse")]
+    pub reverse: bool,
+
+    /// Leave entries unsorted, in directory order (same as --sort=none)
+    #[arg(short = 'U', long = "unsorted")]
+    pub unsorted: bool,
+
+    /// List directories before files
+    #[arg(long = "dirsfirst")]
+    pub dirsfirst: bool,
+
+    /// List files before directories
+    #[arg(long = "filesfirst")]
+    pub filesfirst: bool,
 
     /// Show owner name or UID for each file
     #[arg(short = 'u', long = "uid")]
@@ -98,6 +173,26 @@ pub struct Args {
     #[arg(long = "json")]
     pub json: bool,
 
+    /// Output results in JSON format compatible with GNU tree (same as --json --json-format=gnu)
+    #[arg(short = 'J')]
+    pub gnu_json: bool,
+
+    /// Schema of the JSON output (implies --json)
+    #[arg(long = "json-format", value_enum)]
+    pub json_format: Option<JsonFormat>,
+
+    /// Output results in XML format, compatible with GNU tree
+    #[arg(short = 'X', long = "xml")]
+    pub xml: bool,
+
+    /// Output results in HTML format, with hyperlinks relative to BASEHREF
+    #[arg(short = 'H', long = "html", value_name = "BASEHREF")]
+    pub html: Option<String>,
+
+    /// Title of the HTML output
+    #[arg(short = 'T', long = "title")]
+    pub title: Option<String>,
+
     /// Display processing progress
     #[arg(long = "progress")]
     pub progress: bool,
@@ -105,6 +200,160 @@ pub struct Args {
     /// Number of threads for parallel processing
     #[arg(long = "threads")]
     pub threads: Option<usize>,
+
+    /// Skip files ignored by .gitignore, .ignore, .git/info/exclude and the global excludes file
+    #[arg(long = "gitignore")]
+    pub gitignore: bool,
+
+    /// Read the paths to display from FILE (stdin if omitted or -) instead of the filesystem
+    #[arg(
+        long = "fromfile",
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = "-"
+    )]
+    pub fromfile: Option<PathBuf>,
+
+    /// Send output to the given file instead of stdout
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+}
+
+/// JSON输出的结构
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonFormat {
+    /// Nested objects with name, path, is_dir, size, modified and children
+    #[default]
+    Native,
+    /// Array of objects with type and contents plus a trailing report, as GNU tree -J
+    Gnu,
+    /// One object per line for each entry, written as the walk discovers it
+    Ndjson,
+}
+
+/// `--du`统计目录大小的方式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuMode {
+    /// Sum of file sizes in bytes, as du --apparent-size
+    #[default]
+    Apparent,
+    /// Disk space actually allocated in blocks, as du
+    Allocated,
+}
+
+impl DuMode {
+    /// 按统计方式取条目的大小
+    pub fn size_of(self, meta: &FileMeta) -> u64 {
+        match self {
+            DuMode::Apparent => meta.size,
+            // 没有块数信息的平台退回到文件大小
+            DuMode::Allocated => meta.blocks.map_or(meta.size, |blocks| blocks * 512),
+        }
+    }
+}
+
+/// 同一目录下条目的排序方式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Alphabetically by name
+    #[default]
+    Name,
+    /// By name, comparing runs of digits as numbers (file2 before file10)
+    Version,
+    /// By size, largest first
+    Size,
+    /// By modification time, newest first
+    #[value(alias = "time")]
+    Mtime,
+    /// By status change time, newest first
+    Ctime,
+    /// By extension, then by name
+    Extension,
+    /// Directory order, unsorted
+    None,
+}
+
+/// `-D`显示的时间戳
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeField {
+    /// Last modification time
+    #[default]
+    #[value(alias = "modified")]
+    Mtime,
+    /// Last access time
+    #[value(alias = "access")]
+    Atime,
+    /// Last status change time
+    #[value(alias = "change")]
+    Ctime,
+    /// Creation time, where the filesystem records it
+    #[value(alias = "creation")]
+    Birth,
+}
+
+impl TimeField {
+    /// 取条目对应的时间戳
+    pub fn time_of(self, meta: &FileMeta) -> Option<SystemTime> {
+        match self {
+            TimeField::Mtime => meta.modified,
+            TimeField::Atime => meta.accessed,
+            TimeField::Ctime => meta.changed,
+            TimeField::Birth => meta.created,
+        }
+    }
+}
+
+/// 创建配置时的错误
+#[derive(Debug)]
+pub enum ConfigError {
+    /// 命令行参数无法解析，包括`--help`和`--version`请求
+    Args(clap::Error),
+    /// 排除或包含模式不是合法的glob或正则表达式
+    InvalidPattern {
+        option: &'static str,
+        pattern: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// 选项的取值无效
+    InvalidValue {
+        option: &'static str,
+        message: String,
+    },
+    /// 两个选项不能同时使用
+    Conflict(&'static str, &'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Args(err) => write!(f, "{}", err),
+            ConfigError::InvalidPattern {
+                option,
+                pattern,
+                source,
+            } => write!(
+                f,
+                "invalid pattern for {}: '{}': {}",
+                option, pattern, source
+            ),
+            ConfigError::InvalidValue { option, message } => {
+                write!(f, "invalid value for {}: {}", option, message)
+            }
+            ConfigError::Conflict(first, second) => {
+                write!(f, "{} cannot be used with {}", first, second)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Args(err) => Some(err),
+            ConfigError::InvalidPattern { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
 }
 
 /// 配置结构，存储处理后的命令行参数
@@ -112,15 +361,107 @@ pub struct Args {
 pub struct Config {
     pub args: Args,
     pub color_enabled: bool,
-    pub exclude_regex: Option<regex::Regex>,
-    pub include_regex: Option<regex::Regex>,
+    pub exclude_pattern: Option<Pattern>,
+    pub include_pattern: Option<Pattern>,
     pub depth: usize, // 处理后的目录深度限制
 }
 
 impl Config {
-    /// 从命令行参数创建配置
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let args = Args::parse();
+    /// 从进程的命令行参数创建配置
+    ///
+    /// 参数无法解析时由clap打印用法并退出进程；库调用方应使用`try_parse_from`或`builder`。
+    pub fn new() -> Result<Self, ConfigError> {
+        Self::from_args(Args::parse())
+    }
+
+    /// 从给定的参数列表创建配置，第一个元素是程序名
+    pub fn try_parse_from<I, T>(argv: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Self::from_args(Args::try_parse_from(argv).map_err(ConfigError::Args)?)
+    }
+
+    /// 创建配置构建器，所有选项均为默认值
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// 从已经解析的参数创建配置，检查选项组合并编译正则表达式
+    pub fn from_args(mut args: Args) -> Result<Self, ConfigError> {
+        if args.color && args.no_color {
+            return Err(ConfigError::Conflict("--color", "--no-color"));
+        }
+
+        // -J等价于--json --json-format=gnu，指定了JSON结构时也就是JSON输出
+        if args.gnu_json {
+            // -J已经选定了GNU结构，不能再指定其他结构
+            match args.json_format {
+                Some(JsonFormat::Native) => {
+                    return Err(ConfigError::Conflict("-J", "--json-format=native"));
+                }
+                Some(JsonFormat::Ndjson) => {
+                    return Err(ConfigError::Conflict("-J", "--json-format=ndjson"));
+                }
+                Some(JsonFormat::Gnu) | None => {}
+            }
+            args.json_format = Some(JsonFormat::Gnu);
+        }
+        if args.json_format.is_some() {
+            args.json = true;
+        }
+
+        // -U等价于--sort=none
+        if args.unsorted {
+            if args.sort.is_some_and(|key| key != SortKey::None) {
+                return Err(ConfigError::Conflict("-U", "--sort"));
+            }
+            args.sort = Some(SortKey::None);
+        }
+
+        if args.dirsfirst && args.filesfirst {
+            return Err(ConfigError::Conflict("--dirsfirst", "--filesfirst"));
+        }
+
+        // 指定了时间格式或时间戳时显示时间列
+        if args.timefmt.is_some() || args.time.is_some() {
+            args.mtime = true;
+        }
+        if let Some(format) = &args.timefmt
+            && format != RELATIVE_TIMEFMT
+            && StrftimeItems::new(format).any(|item| item == Item::Error)
+        {
+            return Err(ConfigError::InvalidValue {
+                option: "--timefmt",
+                message: format!("invalid format string '{}'", format),
+            });
+        }
+
+        // --du、-h和--si显示的就是大小列
+        if args.du.is_some() || args.human || args.si {
+            args.size = true;
+        }
+
+        // 只能选择一种输出格式
+        let formats = [
+            ("--json", args.json),
+            ("--xml", args.xml),
+            ("--html", args.html.is_some()),
+        ];
+        let mut selected = formats
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name);
+        if let (Some(first), Some(second)) = (selected.next(), selected.next()) {
+            return Err(ConfigError::Conflict(first, second));
+        }
+        if args.level == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                option: "--level",
+                message: "must be greater than 0".to_string(),
+            });
+        }
 
         // 设置默认深度限制
         const DEFAULT_DEPTH: usize = 3;
@@ -131,29 +472,233 @@ impl Config {
             false
         } else if args.color {
             true
+        } else if args.output.is_some() {
+            // 输出到文件时默认不使用彩色
+            false
         } else {
             // 默认：如果stdout是终端，则启用彩色
             atty::is(atty::Stream::Stdout)
         };
 
-        // 编译排除正则表达式
-        let exclude_regex = match &args.exclude {
-            Some(pattern) => Some(regex::Regex::new(pattern)?),
-            None => None,
-        };
-
-        // 编译包含正则表达式
-        let include_regex = match &args.include {
-            Some(pattern) => Some(regex::Regex::new(pattern)?),
-            None => None,
-        };
+        // 编译排除和包含模式
+        let exclude_pattern = compile_pattern("--exclude", &args.exclude, &args)?;
+        let include_pattern = compile_pattern("--include", &args.include, &args)?;
 
         Ok(Self {
             args,
             color_enabled,
-            exclude_regex,
-            include_regex,
+            exclude_pattern,
+            include_pattern,
             depth,
         })
     }
 }
+
+/// 编译模式选项，默认为glob，`--regex`时为正则表达式；没有指定模式时为None
+fn compile_pattern(
+    option: &'static str,
+    patterns: &[String],
+    args: &Args,
+) -> Result<Option<Pattern>, ConfigError> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let pattern = if args.regex {
+        Pattern::regex(patterns, args.ignore_case)
+    } else {
+        Pattern::glob(patterns, args.ignore_case)
+    };
+    pattern
+        .map(Some)
+        .map_err(|err| ConfigError::InvalidPattern {
+            option,
+            pattern: err.pattern,
+            source: err.source,
+        })
+}
+
+/// 以编程方式创建配置，不读取进程的命令行参数
+///
+/// 每个setter对应`Args`中的一个选项，`build`时与命令行一样检查选项组合。
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    args: Args,
+}
+
+/// 为布尔选项生成setter
+macro_rules! flag_setters {
+    ($($(#[$doc:meta])* $name:ident;)*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(mut self, enabled: bool) -> Self {
+                self.args.$name = enabled;
+                self
+            }
+        )*
+    };
+}
+
+impl ConfigBuilder {
+    flag_setters! {
+        /// 显示隐藏文件（`-a`）
+        all;
+        /// 使用ASCII字符绘制树线（`-A`）
+        ascii;
+        /// 强制彩色输出（`-C`）
+        color;
+        /// 只显示目录（`-d`）
+        dirs_only;
+        /// 显示修改时间（`-D`）
+        mtime;
+        /// 在文件名后追加类型标识（`-F`）
+        classify;
+        /// 反向排序（`-r`）
+        reverse;
+        /// 不排序，保持目录中的顺序（`-U`）
+        unsorted;
+        /// 排序和匹配模式时忽略大小写（`--ignore-case`）
+        ignore_case;
+        /// 将`-I`/`-P`作为正则表达式（`--regex`）
+        regex;
+        /// 用相对路径而不是名称匹配`-I`/`-P`（`--match-path`）
+        match_path;
+        /// 目录排在文件前面（`--dirsfirst`）
+        dirsfirst;
+        /// 文件排在目录前面（`--filesfirst`）
+        filesfirst;
+        /// 以UTC而不是本地时区显示时间（`--utc`）
+        utc;
+        /// 显示完整路径（`-f`）
+        full_path;
+        /// 显示所属组（`-g`）
+        gid;
+        /// 不显示缩进线和汇总（`-i`）
+        noreport;
+        /// 跟随符号链接（`-l`）
+        follow_links;
+        /// 禁用彩色输出（`-n`）
+        no_color;
+        /// 原样输出不可打印字符（`-N`）
+        literal;
+        /// 显示权限（`-p`）
+        perms;
+        /// 用'?'替换不可打印字符（`-q`）
+        quiet;
+        /// 显示文件大小（`-s`）
+        size;
+        /// 以1024为进制显示人性化大小（`-h`）
+        human;
+        /// 以1000为进制显示人性化大小（`--si`）
+        si;
+        /// 显示所有者（`-u`）
+        uid;
+        /// 不跨越文件系统（`-x`）
+        samefilesystem;
+        /// 以JSON格式输出（`--json`）
+        json;
+        /// 以GNU tree兼容的JSON格式输出（`-J`）
+        gnu_json;
+        /// 以XML格式输出（`-X`）
+        xml;
+        /// 显示处理进度（`--progress`）
+        progress;
+        /// 跳过被忽略规则匹配的文件（`--gitignore`）
+        gitignore;
+    }
+
+    /// 排除匹配该模式的条目（`-I`），可以多次调用
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.args.exclude.push(pattern.into());
+        self
+    }
+
+    /// 只显示匹配该模式的文件（`-P`），可以多次调用
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.args.include.push(pattern.into());
+        self
+    }
+
+    /// 时间的strftime格式，`relative`表示相对时间（`--timefmt`）
+    pub fn timefmt(mut self, format: impl Into<String>) -> Self {
+        self.args.timefmt = Some(format.into());
+        self
+    }
+
+    /// 时间列显示的时间戳（`--time`）
+    pub fn time(mut self, field: TimeField) -> Self {
+        self.args.time = Some(field);
+        self
+    }
+
+    /// 汇总每个目录下所有内容的大小（`--du`）
+    pub fn du(mut self, mode: DuMode) -> Self {
+        self.args.du = Some(mode);
+        self
+    }
+
+    /// 不进入条目数超过`limit`的目录（`--filelimit`）
+    pub fn filelimit(mut self, limit: usize) -> Self {
+        self.args.filelimit = Some(limit);
+        self
+    }
+
+    /// 排序方式（`-t`）
+    pub fn sort(mut self, key: SortKey) -> Self {
+        self.args.sort = Some(key);
+        self
+    }
+
+    /// 目录深度限制（`-L`）
+    pub fn level(mut self, level: usize) -> Self {
+        self.args.level = Some(level);
+        self
+    }
+
+    /// 起始目录
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.path = Some(path.into());
+        self
+    }
+
+    /// JSON输出的结构（`--json-format`），同时启用JSON输出
+    pub fn json_format(mut self, format: JsonFormat) -> Self {
+        self.args.json_format = Some(format);
+        self
+    }
+
+    /// 以HTML格式输出，链接相对于给定的基础URL（`-H`）
+    pub fn html(mut self, base_href: impl Into<String>) -> Self {
+        self.args.html = Some(base_href.into());
+        self
+    }
+
+    /// HTML输出的标题（`-T`）
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.args.title = Some(title.into());
+        self
+    }
+
+    /// 并行处理的线程数（`--threads`）
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.args.threads = Some(threads);
+        self
+    }
+
+    /// 从路径列表文件读取目录树，`-`表示标准输入（`--fromfile`）
+    pub fn fromfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.fromfile = Some(path.into());
+        self
+    }
+
+    /// 输出文件（`-o`）
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.output = Some(path.into());
+        self
+    }
+
+    /// 检查选项并创建配置
+    pub fn build(self) -> Result<Config, ConfigError> {
+        Config::from_args(self.args)
+    }
+}
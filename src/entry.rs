@@ -0,0 +1,395 @@
+//! 目录条目
+//!
+//! 遍历结果使用自有的条目类型，而不是直接暴露`walkdir::DirEntry`或标准库的元数据。
+//! 条目中的文件类型和元数据都是普通的值，既可以从文件系统读取，
+//! 也可以由调用方根据归档、清单或远程列表自行构造。
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 文件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Socket,
+    Fifo,
+    BlockDevice,
+    CharDevice,
+    /// 无法识别的类型
+    Other,
+}
+
+impl FileKind {
+    /// 从标准库的文件类型转换
+    pub fn from_std(file_type: fs::FileType) -> Self {
+        if file_type.is_dir() {
+            return FileKind::Dir;
+        }
+        if file_type.is_symlink() {
+            return FileKind::Symlink;
+        }
+        if file_type.is_file() {
+            return FileKind::File;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if file_type.is_socket() {
+                return FileKind::Socket;
+            }
+            if file_type.is_fifo() {
+                return FileKind::Fifo;
+            }
+            if file_type.is_block_device() {
+                return FileKind::BlockDevice;
+            }
+            if file_type.is_char_device() {
+                return FileKind::CharDevice;
+            }
+        }
+
+        FileKind::Other
+    }
+
+    /// 是否是目录
+    pub fn is_dir(self) -> bool {
+        self == FileKind::Dir
+    }
+
+    /// 是否是普通文件
+    pub fn is_file(self) -> bool {
+        self == FileKind::File
+    }
+
+    /// 是否是符号链接
+    pub fn is_symlink(self) -> bool {
+        self == FileKind::Symlink
+    }
+}
+
+/// 条目的元数据
+///
+/// 所有字段都是普通值，没有对应信息的来源（例如非Unix平台或路径清单）留空即可。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileMeta {
+    /// 文件大小（字节）
+    pub size: u64,
+    /// Unix模式位（包含文件类型位）
+    pub mode: Option<u32>,
+    /// 所有者UID
+    pub uid: Option<u32>,
+    /// 所属组GID
+    pub gid: Option<u32>,
+    /// 最后修改时间
+    pub modified: Option<SystemTime>,
+    /// 最后访问时间
+    pub accessed: Option<SystemTime>,
+    /// 状态变更时间（ctime）
+    pub changed: Option<SystemTime>,
+    /// 创建时间
+    pub created: Option<SystemTime>,
+    /// 所在设备ID
+    pub dev: Option<u64>,
+    /// inode编号
+    pub ino: Option<u64>,
+    /// 实际占用的512字节块数
+    pub blocks: Option<u64>,
+}
+
+impl FileMeta {
+    /// 从标准库的元数据转换
+    pub fn from_std(meta: &Metadata) -> Self {
+        #[allow(unused_mut)]
+        let mut result = Self {
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            accessed: meta.accessed().ok(),
+            created: meta.created().ok(),
+            ..Self::default()
+        };
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            result.mode = Some(meta.mode());
+            result.uid = Some(meta.uid());
+            result.gid = Some(meta.gid());
+            result.changed = u64::try_from(meta.ctime())
+                .ok()
+                .map(|secs| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+            result.dev = Some(meta.dev());
+            result.ino = Some(meta.ino());
+            result.blocks = Some(meta.blocks());
+        }
+
+        result
+    }
+}
+
+/// 目录树中的单个条目
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    file_name: OsString,
+    depth: usize,
+    file_type: FileKind,
+    metadata: Option<FileMeta>,
+    metadata_error: Option<String>,
+    error: Option<String>,
+    filelimit_exceeded: Option<usize>,
+    link_target: Option<PathBuf>,
+    broken_link: bool,
+    recursive_link: bool,
+}
+
+impl Entry {
+    /// 创建条目，文件名取自路径的最后一个组成部分
+    pub fn new(path: impl Into<PathBuf>, depth: usize, file_type: FileKind) -> Self {
+        let path = path.into();
+        Self {
+            file_name: path.file_name().unwrap_or(path.as_os_str()).to_os_string(),
+            path,
+            depth,
+            file_type,
+            metadata: None,
+            metadata_error: None,
+            error: None,
+            filelimit_exceeded: None,
+            link_target: None,
+            broken_link: false,
+            recursive_link: false,
+        }
+    }
+
+    /// 附加元数据
+    pub fn with_metadata(mut self, metadata: FileMeta) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// 从walkdir条目创建，元数据在此时读取并缓存
+    pub fn from_walkdir(entry: &walkdir::DirEntry) -> Self {
+        let (metadata, metadata_error) = match entry.metadata() {
+            Ok(meta) => (Some(FileMeta::from_std(&meta)), None),
+            Err(err) => (None, Some(walkdir_error_message(&err))),
+        };
+        let mut result = Self {
+            path: entry.path().to_path_buf(),
+            file_name: entry.file_name().to_os_string(),
+            depth: entry.depth(),
+            file_type: FileKind::from_std(entry.file_type()),
+            metadata,
+            metadata_error,
+            error: None,
+            filelimit_exceeded: None,
+            link_target: None,
+            broken_link: false,
+            recursive_link: false,
+        };
+        if entry.path_is_symlink() {
+            result.read_link_target();
+        }
+        result
+    }
+
+    /// 从标准库的目录条目创建
+    ///
+    /// `follow_links`为true时，符号链接会被解析为其指向的目标；
+    /// 目标不存在的悬空链接仍然作为符号链接保留。无法读取文件类型时返回错误。
+    pub fn from_dir_entry(
+        entry: &fs::DirEntry,
+        depth: usize,
+        follow_links: bool,
+    ) -> io::Result<Self> {
+        let path = entry.path();
+        let mut file_type = entry.file_type()?;
+        let is_symlink = file_type.is_symlink();
+        let mut metadata = None;
+
+        if follow_links
+            && file_type.is_symlink()
+            && let Ok(target_meta) = fs::metadata(&path)
+        {
+            file_type = target_meta.file_type();
+            metadata = Some(target_meta);
+        }
+
+        // 条目可能在读取目录之后被删除，此时保留条目并记录错误
+        let mut metadata_error = None;
+        if metadata.is_none() {
+            match fs::symlink_metadata(&path) {
+                Ok(meta) => metadata = Some(meta),
+                Err(err) => metadata_error = Some(err.to_string()),
+            }
+        }
+
+        let mut result = Self {
+            path,
+            file_name: entry.file_name(),
+            depth,
+            file_type: FileKind::from_std(file_type),
+            metadata: metadata.map(|meta| FileMeta::from_std(&meta)),
+            metadata_error,
+            error: None,
+            filelimit_exceeded: None,
+            link_target: None,
+            broken_link: false,
+            recursive_link: false,
+        };
+        if is_symlink {
+            result.read_link_target();
+        }
+        Ok(result)
+    }
+
+    /// 从已经读取的元数据创建条目
+    ///
+    /// 适用于调用方自行遍历或缓存了元数据的场景，文件类型取自元数据。
+    pub fn from_metadata(path: PathBuf, depth: usize, metadata: &Metadata) -> Self {
+        Self::new(path, depth, FileKind::from_std(metadata.file_type()))
+            .with_metadata(FileMeta::from_std(metadata))
+    }
+
+    /// 为遍历起始路径创建根条目（深度为0）
+    ///
+    /// 与walkdir一致，根路径是符号链接时总是跟随它。
+    pub fn from_root(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path).or_else(|_| fs::symlink_metadata(path))?;
+        let mut result = Self::from_metadata(path.to_path_buf(), 0, &metadata);
+        if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+            result.read_link_target();
+        }
+        Ok(result)
+    }
+
+    /// 记录无法读取元数据的原因，用于非文件系统来源的条目
+    pub fn with_metadata_error(mut self, message: impl Into<String>) -> Self {
+        self.metadata_error = Some(message.into());
+        self
+    }
+
+    /// 附加符号链接的目标，用于非文件系统来源的条目
+    pub fn with_link_target(mut self, target: impl Into<PathBuf>) -> Self {
+        self.link_target = Some(target.into());
+        self
+    }
+
+    /// 读取符号链接的目标，并检查目标是否存在
+    fn read_link_target(&mut self) {
+        self.link_target = fs::read_link(&self.path).ok();
+        self.broken_link = fs::metadata(&self.path).is_err();
+    }
+
+    /// 条目的完整路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 条目的文件名
+    pub fn file_name(&self) -> &OsStr {
+        &self.file_name
+    }
+
+    /// 相对于树根的深度，根条目为0
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// 文件类型
+    pub fn file_type(&self) -> FileKind {
+        self.file_type
+    }
+
+    /// 缓存的元数据，读取失败或来源不提供时为None
+    pub fn metadata(&self) -> Option<&FileMeta> {
+        self.metadata.as_ref()
+    }
+
+    /// 读取元数据时发生的错误，例如条目在遍历过程中被删除
+    ///
+    /// 来源本身不提供元数据（例如没有大小列的路径清单）时为None。
+    pub fn metadata_error(&self) -> Option<&str> {
+        self.metadata_error.as_deref()
+    }
+
+    /// 读取该目录时发生的错误，例如权限不足
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// 符号链接指向的目标（链接中保存的原始路径），不是符号链接时为None
+    ///
+    /// 使用`-l`跟随链接时，条目的文件类型和元数据是目标的，但仍然保留链接目标。
+    pub fn link_target(&self) -> Option<&Path> {
+        self.link_target.as_deref()
+    }
+
+    /// 是否是目标不存在的悬空链接
+    pub fn is_broken_link(&self) -> bool {
+        self.broken_link
+    }
+
+    /// 是否是因指向自己的上级目录而没有跟随的链接
+    pub fn is_recursive_link(&self) -> bool {
+        self.recursive_link
+    }
+
+    /// 目录的条目数超过`--filelimit`而没有进入时，返回其条目数
+    pub fn filelimit_exceeded(&self) -> Option<usize> {
+        self.filelimit_exceeded
+    }
+
+    /// 记录读取该目录时发生的错误
+    pub(crate) fn set_error(&mut self, message: String) {
+        self.error = Some(message);
+    }
+
+    /// 替换条目的大小，用于`--du`汇总目录大小
+    pub(crate) fn set_size(&mut self, size: u64) {
+        self.metadata.get_or_insert_with(FileMeta::default).size = size;
+    }
+
+    /// 记录该链接因形成循环而没有跟随
+    pub(crate) fn set_recursive_link(&mut self) {
+        self.recursive_link = true;
+    }
+
+    /// 记录该目录因条目数超过`--filelimit`而没有进入
+    pub(crate) fn set_filelimit_exceeded(&mut self, count: usize) {
+        self.filelimit_exceeded = Some(count);
+    }
+
+    /// 替换元数据，用于路径列表中重复出现的路径
+    pub(crate) fn set_metadata(&mut self, metadata: Option<FileMeta>) {
+        self.metadata = metadata;
+    }
+
+    /// 修改文件类型，用于路径列表中后来才发现有子条目的路径
+    pub(crate) fn set_file_type(&mut self, file_type: FileKind) {
+        self.file_type = file_type;
+    }
+
+    /// 文件所在设备的ID，用于`-x`判断是否跨越文件系统
+    pub(crate) fn device(&self) -> Option<u64> {
+        self.metadata.as_ref().and_then(|meta| meta.dev)
+    }
+
+    /// 文件的唯一标识（设备ID和inode编号），用于检测跟随链接时的循环
+    pub(crate) fn file_id(&self) -> Option<(u64, u64)> {
+        let meta = self.metadata.as_ref()?;
+        Some((meta.dev?, meta.ino?))
+    }
+}
+
+/// 提取walkdir错误的描述，优先使用底层I/O错误信息
+pub(crate) fn walkdir_error_message(err: &walkdir::Error) -> String {
+    err.io_error()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| err.to_string())
+}
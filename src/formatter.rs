@@ -1,8 +1,25 @@
-use crate::config::Config;
+use crate::config::{Config, JsonFormat, RELATIVE_TIMEFMT};
+use crate::entry::{Entry, FileKind};
+use crate::perms::{self, OwnerCache};
+use crate::tree::TreeNode;
+use crate::walker::WalkError;
+use chrono::{DateTime, Local, Utc};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
-use walkdir::DirEntry;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// HTML输出的默认标题，与GNU tree一致
+const HTML_TITLE: &str = "Directory Tree";
+
+/// HTML输出的内联样式
+const HTML_STYLE: &str = "body { font-family: monospace; }
+.tree details details, .tree details .file { margin-left: 1.5em; }
+.tree summary { cursor: pointer; }
+.meta { color: #666; white-space: pre; }
+.error { color: #c00; }
+a { text-decoration: none; }
+a:hover { text-decoration: underline; }";
 
 /// JSON输出的文件结构
 #[derive(Serialize, Deserialize, Debug)]
@@ -13,6 +30,81 @@ pub struct FileNode {
     pub size: u64,
     pub modified: u64,
     pub children: Vec<FileNode>,
+    /// 符号链接的目标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// 读取该目录时发生的错误
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// JSON输出的顶层结构：根节点及遍历过程中遇到的错误列表
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    #[serde(flatten)]
+    tree: FileNode,
+    #[serde(skip_serializing_if = "<[WalkError]>::is_empty")]
+    errors: &'a [WalkError],
+}
+
+/// NDJSON输出中的一行，对应一个条目
+#[derive(Serialize)]
+struct NdjsonEntry<'a> {
+    path: String,
+    depth: usize,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: Option<u64>,
+    mtime: Option<u64>,
+    /// 父目录路径，根条目为null
+    parent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+/// GNU tree `-J`格式中的条目
+#[derive(Serialize, Debug)]
+struct GnuJsonEntry {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prot: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// 目录的子条目，非目录时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contents: Option<Vec<GnuJsonEntry>>,
+}
+
+/// GNU tree `-J`格式顶层数组的元素：根目录或末尾的统计对象
+#[derive(Serialize)]
+#[serde(untagged)]
+enum GnuJsonItem {
+    Entry(Box<GnuJsonEntry>),
+    Report {
+        #[serde(rename = "type")]
+        kind: &'static str,
+        directories: usize,
+        files: usize,
+        /// `--du`时的总大小
+        #[serde(skip_serializing_if = "Option::is_none")]
+        size: Option<u64>,
+    },
 }
 
 /// 输出格式化引擎
@@ -21,6 +113,19 @@ pub struct Formatter {
 
     // 用于跟踪目录树结构的状态
     last_entries: Vec<bool>,
+
+    // 已输出的目录和文件数量，用于摘要
+    total_dirs: usize,
+    total_files: usize,
+
+    // --du时根目录的总大小
+    du_total: Option<u64>,
+
+    // XML输出中尚未闭合的<directory>元素数量
+    open_dirs: usize,
+
+    // 用户名/组名缓存
+    owners: OwnerCache,
 }
 
 impl Formatter {
@@ -29,11 +134,56 @@ impl Formatter {
         Self {
             config,
             last_entries: Vec::new(),
+            total_dirs: 0,
+            total_files: 0,
+            du_total: None,
+            open_dirs: 0,
+            owners: OwnerCache::new(),
         }
     }
 
     /// 格式化单个目录条目
-    fn format_entry(&self, entry: &DirEntry) -> String {
+    fn format_entry(&self, entry: &Entry) -> String {
+        let mut result = self.format_columns(entry);
+
+        // 添加文件名
+        result.push_str(&self.format_filename(entry));
+
+        // 符号链接显示其目标
+        if let Some(target) = entry.link_target() {
+            result.push_str(" -> ");
+            result.push_str(&target.display().to_string());
+        }
+
+        // 标记无法打开或没有进入的目录（与GNU tree一致）
+        if let Some(note) = self.dir_note(entry) {
+            result.push_str(&format!(" [{}]", note));
+        }
+
+        result
+    }
+
+    /// 条目后面的说明：目录无法打开、链接形成循环、条目数超过`--filelimit`，或无法读取元数据
+    fn dir_note(&self, entry: &Entry) -> Option<String> {
+        if entry.error().is_some() {
+            return Some("error opening dir".to_string());
+        }
+        if entry.is_recursive_link() {
+            return Some("recursive, not followed".to_string());
+        }
+        if let Some(count) = entry.filelimit_exceeded() {
+            return Some(format!(
+                "{} entries exceeds filelimit, not opening dir",
+                count
+            ));
+        }
+        entry
+            .metadata_error()
+            .map(|_| "cannot read metadata".to_string())
+    }
+
+    /// 格式化文件名之前的元数据列（权限、所有者、组、大小、时间）
+    fn format_columns(&self, entry: &Entry) -> String {
         let mut result = String::new();
 
         // 添加文件权限
@@ -66,15 +216,16 @@ impl Formatter {
             result.push_str("  ");
         }
 
-        // 添加文件名
-        result.push_str(&self.format_filename(entry));
-
         result
     }
 
     /// 格式化文件权限
-    fn format_perms(&self, entry: &DirEntry) -> String {
-        // 在Windows上简化处理，返回基本的文件类型信息
+    fn format_perms(&self, entry: &Entry) -> String {
+        if let Some(mode) = entry.metadata().and_then(|meta| meta.mode) {
+            return perms::format_mode(mode);
+        }
+
+        // 非Unix平台或无法读取元数据时，根据文件类型给出近似值
         if entry.file_type().is_dir() {
             "drwxr-xr-x".to_string()
         } else if entry.file_type().is_symlink() {
@@ -85,97 +236,122 @@ impl Formatter {
     }
 
     /// 格式化文件所有者
-    fn format_uid(&self, _entry: &DirEntry) -> String {
-        // 在Windows上简化处理，返回当前用户名
-        "user".to_string()
+    fn format_uid(&self, entry: &Entry) -> String {
+        match entry.metadata().and_then(|meta| meta.uid) {
+            Some(uid) => self.owners.user_name(uid),
+            None => "?".to_string(),
+        }
     }
 
     /// 格式化文件所属组
-    fn format_gid(&self, _entry: &DirEntry) -> String {
-        // 在Windows上简化处理，返回当前用户组
-        "group".to_string()
+    fn format_gid(&self, entry: &Entry) -> String {
+        match entry.metadata().and_then(|meta| meta.gid) {
+            Some(gid) => self.owners.group_name(gid),
+            None => "?".to_string(),
+        }
     }
 
-    /// 格式化文件大小
-    fn format_size(&self, entry: &DirEntry) -> String {
-        if entry.file_type().is_dir() {
-            return "".to_string();
-        }
+    /// 格式化文件大小，右对齐以便各行的大小列对齐
+    fn format_size(&self, entry: &Entry) -> String {
+        // 与GNU tree相同：没有--du时目录显示其自身的大小
+        let size = match entry.metadata() {
+            Some(meta) => self.humanize_size(meta.size),
+            None => String::new(),
+        };
 
-        match entry.metadata() {
-            Ok(meta) => {
-                let size = meta.len();
-                self.humanize_size(size)
-            }
-            Err(_) => "".to_string(),
+        // 与GNU tree相同：字节数占11列，人性化大小占4列
+        let width = if self.human_base().is_some() { 4 } else { 11 };
+        format!("{:>width$}", size, width = width)
+    }
+
+    /// 人性化大小的进制，`None`表示显示字节数
+    fn human_base(&self) -> Option<u64> {
+        if self.config.args.si {
+            Some(1000)
+        } else if self.config.args.human {
+            Some(1024)
+        } else {
+            None
         }
     }
 
-    /// 手动实现文件大小格式化
+    /// 按`-h`/`--si`格式化大小，否则为字节数
     fn humanize_size(&self, size: u64) -> String {
-        const KB: u64 = 1024;
-        const MB: u64 = KB * 1024;
-        const GB: u64 = MB * 1024;
-
-        if size < KB {
-            format!("{} B", size)
-        } else if size < MB {
-            format!("{:.1} KB", size as f64 / KB as f64)
-        } else if size < GB {
-            format!("{:.1} MB", size as f64 / MB as f64)
+        // --si使用小写的k，与GNU tree一致
+        const UNITS: [&str; 6] = ["", "K", "M", "G", "T", "P"];
+        const SI_UNITS: [&str; 6] = ["", "k", "M", "G", "T", "P"];
+
+        let Some(base) = self.human_base() else {
+            return size.to_string();
+        };
+        let units = if base == 1000 { SI_UNITS } else { UNITS };
+
+        if size < base {
+            return size.to_string();
+        }
+        let mut value = size as f64;
+        let mut unit = 0;
+        while value >= base as f64 && unit < units.len() - 1 {
+            value /= base as f64;
+            unit += 1;
+        }
+
+        // 小于10时保留一位小数，否则取整
+        if value < 9.95 {
+            format!("{:.1}{}", value, units[unit])
         } else {
-            format!("{:.1} GB", size as f64 / GB as f64)
+            format!("{:.0}{}", value, units[unit])
         }
     }
 
-    /// 格式化修改时间
-    fn format_mtime(&self, entry: &DirEntry) -> String {
-        match entry.metadata() {
-            Ok(meta) => match meta.modified() {
-                Ok(time) => self.format_time(time),
-                Err(_) => "".to_string(),
-            },
-            Err(_) => "".to_string(),
+    /// 格式化时间列，显示`--time`选择的时间戳
+    fn format_mtime(&self, entry: &Entry) -> String {
+        let field = self.config.args.time.unwrap_or_default();
+        match entry.metadata().and_then(|meta| field.time_of(meta)) {
+            Some(time) => self.format_time(time),
+            None => "".to_string(),
         }
     }
 
-    /// 手动实现时间格式化
+    /// 按`--timefmt`格式化时间，默认与GNU tree相同：
+    /// 半年内的时间显示到分钟，更早或未来的时间显示年份
     fn format_time(&self, time: SystemTime) -> String {
-        match time.duration_since(UNIX_EPOCH) {
-            Ok(duration) => {
-                // 简化实现，返回UNIX时间戳
-                format!("{}", duration.as_secs())
-            }
-            Err(_) => "".to_string(),
+        const SIX_MONTHS: Duration = Duration::from_secs(6 * 31 * 24 * 60 * 60);
+
+        let now = SystemTime::now();
+        let format = match self.config.args.timefmt.as_deref() {
+            Some(RELATIVE_TIMEFMT) => return relative_time(time, now),
+            Some(format) => format,
+            None => match now.duration_since(time) {
+                Ok(age) if age <= SIX_MONTHS => "%b %e %H:%M",
+                _ => "%b %e  %Y",
+            },
+        };
+
+        let utc = DateTime::<Utc>::from(time);
+        if self.config.args.utc {
+            utc.format(format).to_string()
+        } else {
+            utc.with_timezone(&Local).format(format).to_string()
         }
     }
 
     /// 检查文件是否可执行
-    fn is_executable(&self, entry: &DirEntry) -> bool {
-        // 在Windows上，检查.exe扩展名
-        #[cfg(target_os = "windows")]
-        {
-            if let Some(filename) = entry.file_name().to_str() {
-                return filename.to_lowercase().ends_with(".exe");
-            }
-            false
+    fn is_executable(&self, entry: &Entry) -> bool {
+        // 有Unix模式位时检查执行权限（owner/group/others任意一个有执行权限）
+        if let Some(mode) = entry.metadata().and_then(|meta| meta.mode) {
+            return (mode & 0o111) != 0;
         }
 
-        // 在Linux/macOS上，检查执行权限
-        #[cfg(not(target_os = "windows"))]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            if let Ok(metadata) = entry.metadata() {
-                let permissions = metadata.permissions();
-                // 检查是否有执行权限位（owner/group/others任意一个有执行权限）
-                return (permissions.mode() & 0o111) != 0;
-            }
-            false
-        }
+        // 没有模式位时（例如Windows），检查.exe扩展名
+        entry
+            .file_name()
+            .to_str()
+            .is_some_and(|filename| filename.to_lowercase().ends_with(".exe"))
     }
 
     /// 格式化文件名
-    fn format_filename(&self, entry: &DirEntry) -> String {
+    fn format_filename(&self, entry: &Entry) -> String {
         let mut filename = if self.config.args.full_path {
             entry.path().display().to_string()
         } else {
@@ -183,14 +359,18 @@ impl Formatter {
         };
 
         // 添加文件类型指示符
-        if self.config.args.filelimit {
-            if entry.file_type().is_dir() {
-                filename.push('/');
-            } else if entry.file_type().is_symlink() {
-                filename.push('@');
-            } else if self.is_executable(entry) {
+        if self.config.args.classify {
+            match entry.file_type() {
+                FileKind::Dir => filename.push('/'),
+                // 显示链接目标时已经可以看出是链接，与GNU tree一致不再添加 @
+                FileKind::Symlink if entry.link_target().is_none() => filename.push('@'),
+                // 链接的模式位总是0o777，不能按可执行文件处理
+                FileKind::Symlink => {}
+                FileKind::Socket => filename.push('='),
+                FileKind::Fifo => filename.push('|'),
                 // 可执行文件添加 * 标记
-                filename.push('*');
+                _ if self.is_executable(entry) => filename.push('*'),
+                _ => {}
             }
         }
 
@@ -211,7 +391,10 @@ impl Formatter {
         // 添加彩色输出
         #[cfg(feature = "color")]
         if self.config.color_enabled {
-            if entry.file_type().is_dir() {
+            if entry.is_broken_link() {
+                // 悬空链接使用红色，与ls的orphan配色一致
+                filename = filename.red().to_string();
+            } else if entry.file_type().is_dir() {
                 filename = filename.blue().to_string();
             } else if entry.file_type().is_symlink() {
                 filename = filename.cyan().to_string();
@@ -221,238 +404,650 @@ impl Formatter {
         filename
     }
 
-    /// 格式化目录树
-    pub fn format_tree(&mut self, entries: impl Iterator<Item = DirEntry>) {
-        let entries: Vec<_> = entries.collect();
-        let total_files = entries.iter().filter(|e| !e.file_type().is_dir()).count();
-        let total_dirs = entries.iter().filter(|e| e.file_type().is_dir()).count();
-        let total_dirs_display = if total_dirs > 0 { total_dirs - 1 } else { 0 }; // 减去根目录
+    /// 格式化目录树并写入`out`
+    ///
+    /// 适用于已经构建好的完整目录树（例如并行遍历的结果或由路径列表构建的树）。
+    /// 串行遍历时使用`begin_tree`/`write_tree_entry`/`end_tree`逐条输出。
+    pub fn format_tree(&mut self, out: &mut impl Write, tree: &TreeNode) -> io::Result<()> {
+        self.begin_tree(out)?;
+        let mut result = Ok(());
+        tree.visit(&mut |entry, is_last| {
+            if result.is_ok() {
+                result = self.write_tree_entry(out, entry, is_last);
+            }
+        });
+        result?;
+        self.end_tree(out)
+    }
+
+    /// 格式化目录树并返回字符串
+    pub fn tree_to_string(&mut self, tree: &TreeNode) -> String {
+        let mut buf = Vec::new();
+        // 写入内存缓冲区不会失败
+        let _ = self.format_tree(&mut buf, tree);
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    /// 开始输出一棵新的目录树，重置缩进状态和计数
+    pub fn begin_tree(&mut self, out: &mut impl Write) -> io::Result<()> {
+        self.last_entries.clear();
+        self.total_dirs = 0;
+        self.total_files = 0;
+        self.du_total = None;
+        self.open_dirs = 0;
+
+        if self.config.args.xml {
+            writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+            writeln!(out, "<tree>")?;
+        } else if self.config.args.html.is_some() {
+            self.begin_html(out)?;
+        }
+
+        Ok(())
+    }
+
+    /// 输出目录树中的一个条目
+    ///
+    /// 条目必须按深度优先的显示顺序传入，`is_last`表示它是否是父目录中的最后一个子条目。
+    pub fn write_tree_entry(
+        &mut self,
+        out: &mut impl Write,
+        entry: &Entry,
+        is_last: bool,
+    ) -> io::Result<()> {
+        // 根目录的大小就是--du的总大小
+        if entry.depth() == 0 && self.config.args.du.is_some() {
+            self.du_total = entry.metadata().map(|meta| meta.size);
+        }
+
+        if self.config.args.xml {
+            return self.write_xml_entry(out, entry);
+        }
+        if self.config.args.html.is_some() {
+            return self.write_html_entry(out, entry);
+        }
+        if self.config.args.json_format == Some(JsonFormat::Ndjson) {
+            return self.write_ndjson_entry(out, entry);
+        }
+
+        let depth = entry.depth();
+
+        // 跳过根目录
+        if depth == 0 {
+            return Ok(());
+        }
+
+        if entry.file_type().is_dir() {
+            self.total_dirs += 1;
+        } else {
+            self.total_files += 1;
+        }
 
         // 如果启用noreport模式，只显示文件列表，不显示缩进线和摘要
         if self.config.args.noreport {
-            for entry in entries.iter() {
-                if entry.depth() == 0 {
-                    continue; // 跳过根目录
-                }
-                // 只输出文件名，不带任何前缀
-                let formatted_entry = self.format_entry(entry);
-                println!("{}", formatted_entry);
-            }
-            return;
+            // 只输出文件名，不带任何前缀
+            return writeln!(out, "{}", self.format_entry(entry));
         }
 
-        // 清空last_entries状态
-        self.last_entries.clear();
+        // 确保last_entries长度为depth-1（只记录父目录状态）
+        while self.last_entries.len() >= depth {
+            self.last_entries.pop();
+        }
 
-        // 遍历所有条目并格式化输出
-        for entry in entries.iter() {
-            let depth = entry.depth();
+        // 生成前缀
+        let mut prefix = String::new();
 
-            // 跳过根目录
-            if depth == 0 {
-                continue;
+        // 为每个父深度添加前缀
+        for &is_last_parent in &self.last_entries {
+            if is_last_parent {
+                prefix.push_str("    ");
+            } else {
+                prefix.push_str("│   ");
             }
+        }
 
-            // 确保last_entries长度为depth-1（只记录父目录状态）
-            while self.last_entries.len() >= depth {
-                self.last_entries.pop();
-            }
+        // 添加当前级别的前缀
+        if is_last {
+            prefix.push_str("└── ");
+        } else {
+            prefix.push_str("├── ");
+        }
 
-            // 计算当前条目是否是同一父目录下的最后一个条目
-            let current_path = entry.path();
-            let parent_path = match current_path.parent() {
-                Some(p) => p,
-                None => continue,
-            };
-
-            // 首先找到所有同一父目录的条目
-            let mut siblings = Vec::new();
-            for e in &entries {
-                if e.depth() != depth {
-                    continue;
-                }
+        // 如果使用ASCII模式，替换为ASCII字符
+        if self.config.args.ascii {
+            prefix = prefix
+                .replace("└", "`")
+                .replace("├", "|")
+                .replace("─", "-")
+                .replace("│", "|");
+        }
 
-                if let Some(p) = e.path().parent()
-                    && p == parent_path
-                {
-                    siblings.push(e);
-                }
+        // 格式化条目名称
+        let formatted_entry = self.format_entry(entry);
+
+        writeln!(out, "{}{}", prefix, formatted_entry)?;
+
+        // 更新last_entries：只在当前条目是目录时添加状态
+        // 因为只有目录才会有子目录
+        if entry.file_type().is_dir() {
+            self.last_entries.push(is_last);
+        }
+
+        Ok(())
+    }
+
+    /// 结束目录树输出，写入摘要信息
+    pub fn end_tree(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.config.args.xml {
+            return self.end_xml(out);
+        }
+        if self.config.args.html.is_some() {
+            return self.end_html(out);
+        }
+        if self.config.args.json_format == Some(JsonFormat::Ndjson) {
+            return Ok(());
+        }
+
+        if self.config.args.noreport {
+            return Ok(());
+        }
+
+        // 打印摘要信息
+        if self.total_dirs > 0 || self.total_files > 0 {
+            writeln!(out)?;
+            writeln!(out, "{}", self.summary())?;
+        }
+
+        Ok(())
+    }
+
+    /// 目录和文件数量的摘要
+    fn summary(&self) -> String {
+        let total_dirs = self.total_dirs;
+        let total_files = self.total_files;
+
+        // --du时在前面加上总大小，与GNU tree一致
+        let used = match self.du_total {
+            Some(total) if self.human_base().is_some() => {
+                format!("{} used in ", self.humanize_size(total))
             }
+            Some(total) => format!("{} bytes used in ", total),
+            None => String::new(),
+        };
 
-            // 检查当前条目是否是最后一个兄弟
-            let is_last = if let Some(last_sibling) = siblings.last() {
-                last_sibling.path() == entry.path()
+        format!(
+            "{}{} directory{}{} {} file{}",
+            used,
+            total_dirs,
+            if total_dirs != 1 { "s" } else { "" },
+            if total_dirs > 0 && total_files > 0 {
+                ", "
             } else {
-                true
-            };
-
-            // 生成前缀
-            let mut prefix = String::new();
-
-            // 为每个父深度添加前缀
-            for &is_last_parent in &self.last_entries {
-                if is_last_parent {
-                    prefix.push_str("    ");
-                } else {
-                    prefix.push_str("│   ");
-                }
-            }
+                ""
+            },
+            total_files,
+            if total_files != 1 { "s" } else { "" }
+        )
+    }
+
+    /// 以XML格式输出一个条目，与GNU tree的`-X`一致
+    ///
+    /// 目录元素在遇到不属于它的下一个条目时才闭合，因此可以边遍历边输出。
+    fn write_xml_entry(&mut self, out: &mut impl Write, entry: &Entry) -> io::Result<()> {
+        let depth = entry.depth();
+        self.close_dirs(out, depth)?;
 
-            // 添加当前级别的前缀
-            if is_last {
-                prefix.push_str("└── ");
+        if depth > 0 {
+            if entry.file_type().is_dir() {
+                self.total_dirs += 1;
             } else {
-                prefix.push_str("├── ");
+                self.total_files += 1;
             }
+        }
 
-            // 如果使用ASCII模式，替换为ASCII字符
-            if self.config.args.ascii {
-                prefix = prefix
-                    .replace("└", "`")
-                    .replace("├", "|")
-                    .replace("─", "-")
-                    .replace("│", "|");
+        let indent = "  ".repeat(depth + 1);
+        let tag = gnu_type_name(entry.file_type());
+        let name = if self.config.args.full_path || depth == 0 {
+            entry.path().display().to_string()
+        } else {
+            entry.file_name().to_string_lossy().to_string()
+        };
+        write!(out, "{}<{} name=\"{}\"", indent, tag, escape_markup(&name))?;
+        if let Some(target) = entry.link_target() {
+            write!(
+                out,
+                " target=\"{}\"",
+                escape_markup(&target.display().to_string())
+            )?;
+        }
+
+        // 可选属性，顺序与GNU tree一致
+        let meta = entry.metadata();
+        if self.config.args.perms {
+            if let Some(mode) = meta.and_then(|meta| meta.mode) {
+                write!(out, " mode=\"{:04o}\"", mode & 0o7777)?;
             }
+            write!(out, " prot=\"{}\"", self.format_perms(entry))?;
+        }
+        if self.config.args.uid {
+            write!(out, " user=\"{}\"", escape_markup(&self.format_uid(entry)))?;
+        }
+        if self.config.args.gid {
+            write!(out, " group=\"{}\"", escape_markup(&self.format_gid(entry)))?;
+        }
+        if self.config.args.size
+            && let Some(meta) = meta
+        {
+            write!(out, " size=\"{}\"", meta.size)?;
+        }
+        if self.config.args.mtime {
+            write!(out, " time=\"{}\"", self.format_mtime(entry))?;
+        }
+
+        if !entry.file_type().is_dir() {
+            return writeln!(out, "></{}>", tag);
+        }
+
+        writeln!(out, ">")?;
+        if let Some(note) = self.dir_note(entry) {
+            writeln!(out, "{}  <error>{}</error>", indent, note)?;
+        }
+        self.open_dirs += 1;
+        Ok(())
+    }
+
+    /// 闭合深度不小于`depth`的目录元素（XML的`<directory>`或HTML的`<details>`）
+    fn close_dirs(&mut self, out: &mut impl Write, depth: usize) -> io::Result<()> {
+        let tag = if self.config.args.xml {
+            "directory"
+        } else {
+            "details"
+        };
+        while self.open_dirs > depth {
+            self.open_dirs -= 1;
+            writeln!(out, "{}</{}>", "  ".repeat(self.open_dirs + 1), tag)?;
+        }
+        Ok(())
+    }
+
+    /// 闭合所有目录元素并输出`<report>`
+    fn end_xml(&mut self, out: &mut impl Write) -> io::Result<()> {
+        self.close_dirs(out, 0)?;
+
+        if !self.config.args.noreport {
+            writeln!(out, "  <report>")?;
+            writeln!(out, "    <directories>{}</directories>", self.total_dirs)?;
+            writeln!(out, "    <files>{}</files>", self.total_files)?;
+            if let Some(total) = self.du_total {
+                writeln!(out, "    <size>{}</size>", total)?;
+            }
+            writeln!(out, "  </report>")?;
+        }
+
+        writeln!(out, "</tree>")
+    }
 
-            // 格式化条目名称
-            let formatted_entry = self.format_entry(entry);
+    /// 输出HTML文档头，样式内联在文档中，不引用任何外部资源
+    fn begin_html(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let title = escape_markup(self.config.args.title.as_deref().unwrap_or(HTML_TITLE));
 
-            println!("{}{}", prefix, formatted_entry);
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>")?;
+        writeln!(out, "<head>")?;
+        writeln!(out, "<meta charset=\"utf-8\">")?;
+        writeln!(out, "<title>{}</title>", title)?;
+        writeln!(out, "<style>")?;
+        writeln!(out, "{}", HTML_STYLE)?;
+        writeln!(out, "</style>")?;
+        writeln!(out, "</head>")?;
+        writeln!(out, "<body>")?;
+        writeln!(out, "<h1>{}</h1>", title)?;
+        writeln!(out, "<div class=\"tree\">")
+    }
 
-            // 更新last_entries：只在当前条目是目录时添加状态
-            // 因为只有目录才会有子目录
+    /// 以HTML格式输出一个条目
+    ///
+    /// 目录输出为可折叠的`<details>`元素，与XML一样在遇到下一个不属于它的条目时闭合。
+    fn write_html_entry(&mut self, out: &mut impl Write, entry: &Entry) -> io::Result<()> {
+        let depth = entry.depth();
+        self.close_dirs(out, depth)?;
+
+        if depth > 0 {
             if entry.file_type().is_dir() {
-                self.last_entries.push(is_last);
+                self.total_dirs += 1;
+            } else {
+                self.total_files += 1;
             }
         }
 
-        // 打印摘要信息
-        if total_dirs_display > 0 || total_files > 0 {
-            println!();
-            println!(
-                "{} directory{}{} {} file{}",
-                total_dirs_display,
-                if total_dirs_display != 1 { "s" } else { "" },
-                if total_dirs_display > 0 && total_files > 0 {
-                    ", "
-                } else {
-                    ""
+        let indent = "  ".repeat(depth + 1);
+        let name = if self.config.args.full_path || depth == 0 {
+            entry.path().display().to_string()
+        } else {
+            entry.file_name().to_string_lossy().to_string()
+        };
+
+        // 链接为基础URL加上相对于树根的路径
+        let base = self.config.args.html.as_deref().unwrap_or_default();
+        let mut href = base.trim_end_matches('/').to_string();
+        let components: Vec<_> = entry.path().components().collect();
+        for component in &components[components.len().saturating_sub(depth)..] {
+            href.push('/');
+            href.push_str(&percent_encode(&component.as_os_str().to_string_lossy()));
+        }
+        if entry.file_type().is_dir() {
+            href.push('/');
+        }
+
+        let mut line = String::new();
+        let columns = self.format_columns(entry);
+        if !columns.is_empty() {
+            line.push_str(&format!(
+                "<span class=\"meta\">{}</span>",
+                escape_markup(&columns)
+            ));
+        }
+        line.push_str(&format!(
+            "<a href=\"{}\">{}</a>",
+            escape_markup(&href),
+            escape_markup(&name)
+        ));
+        if let Some(target) = entry.link_target() {
+            line.push_str(&format!(
+                " -&gt; {}",
+                escape_markup(&target.display().to_string())
+            ));
+        }
+        if let Some(note) = self.dir_note(entry) {
+            line.push_str(&format!(" <span class=\"error\">[{}]</span>", note));
+        }
+
+        if !entry.file_type().is_dir() {
+            return writeln!(out, "{}<div class=\"file\">{}</div>", indent, line);
+        }
+
+        writeln!(out, "{}<details open><summary>{}</summary>", indent, line)?;
+        self.open_dirs += 1;
+        Ok(())
+    }
+
+    /// 闭合所有目录并输出摘要和文档尾
+    fn end_html(&mut self, out: &mut impl Write) -> io::Result<()> {
+        self.close_dirs(out, 0)?;
+        writeln!(out, "</div>")?;
+
+        if !self.config.args.noreport {
+            writeln!(out, "<hr>")?;
+            writeln!(out, "<p class=\"report\">{}</p>", self.summary())?;
+        }
+
+        writeln!(out, "</body>")?;
+        writeln!(out, "</html>")
+    }
+
+    /// 从目录树构建JSON输出的文件节点树
+    pub fn build_file_tree(&self, tree: &TreeNode) -> FileNode {
+        FileNode {
+            name: tree.entry.file_name().to_string_lossy().to_string(),
+            path: tree.entry.path().display().to_string(),
+            is_dir: tree.entry.depth() == 0 || tree.entry.file_type().is_dir(),
+            size: match tree.entry.metadata() {
+                Some(meta) => meta.size,
+                None => 0,
+            },
+            modified: match tree.entry.metadata().and_then(|meta| meta.modified) {
+                Some(time) => match time.duration_since(UNIX_EPOCH) {
+                    Ok(dur) => dur.as_secs(),
+                    Err(_) => 0,
                 },
-                total_files,
-                if total_files != 1 { "s" } else { "" }
-            );
+                None => 0,
+            },
+            children: tree
+                .children
+                .iter()
+                .map(|child| self.build_file_tree(child))
+                .collect(),
+            target: tree
+                .entry
+                .link_target()
+                .map(|target| target.display().to_string()),
+            error: tree
+                .entry
+                .error()
+                .or(tree.entry.metadata_error())
+                .map(str::to_string),
         }
     }
 
-    /// 构建文件节点树
-    fn build_file_tree(&self, entries: &[DirEntry]) -> FileNode {
-        // 找到深度为0的条目（根目录）
-        let root_entries: Vec<&DirEntry> = entries.iter().filter(|e| e.depth() == 0).collect();
+    /// 以JSON格式写入`out`，`errors`为遍历过程中无法读取的路径
+    ///
+    /// 结构由`--json-format`决定；GNU格式不包含`errors`，错误只输出到stderr。
+    pub fn format_json(
+        &self,
+        out: &mut impl Write,
+        tree: Option<&TreeNode>,
+        errors: &[WalkError],
+    ) -> io::Result<()> {
+        match self.config.args.json_format {
+            Some(JsonFormat::Gnu) => return self.format_gnu_json(out, tree),
+            Some(JsonFormat::Ndjson) => {
+                let mut result = Ok(());
+                if let Some(tree) = tree {
+                    tree.visit(&mut |entry, _| {
+                        if result.is_ok() {
+                            result = self.write_ndjson_entry(out, entry);
+                        }
+                    });
+                }
+                return result;
+            }
+            _ => {}
+        }
 
-        if root_entries.is_empty() {
-            // 如果没有根目录，返回空节点
-            return FileNode {
+        // 构建文件树，没有根目录时输出空节点
+        let tree = match tree {
+            Some(tree) => self.build_file_tree(tree),
+            None => FileNode {
                 name: ".".to_string(),
                 path: ".".to_string(),
                 is_dir: true,
                 size: 0,
                 modified: 0,
                 children: Vec::new(),
-            };
-        }
-
-        // 使用第一个根目录作为根节点
-        let root_entry = root_entries[0];
-        let root_node = FileNode {
-            name: root_entry.file_name().to_string_lossy().to_string(),
-            path: root_entry.path().display().to_string(),
-            is_dir: true,
-            size: match root_entry.metadata() {
-                Ok(meta) => meta.len(),
-                Err(_) => 0,
+                target: None,
+                error: None,
             },
-            modified: match root_entry.metadata() {
-                Ok(meta) => match meta.modified() {
-                    Ok(time) => match time.duration_since(UNIX_EPOCH) {
-                        Ok(dur) => dur.as_secs(),
-                        Err(_) => 0,
-                    },
-                    Err(_) => 0,
-                },
-                Err(_) => 0,
-            },
-            children: Vec::new(),
         };
+        let report = JsonReport { tree, errors };
 
-        // 递归构建子节点
-        self.build_children_recursive(root_node, entries, 1)
+        // 序列化为JSON并输出
+        serde_json::to_writer_pretty(&mut *out, &report)?;
+        writeln!(out)
     }
 
-    /// 递归构建子节点
-    fn build_children_recursive(
-        &self,
-        mut parent: FileNode,
-        entries: &[DirEntry],
-        depth: usize,
-    ) -> FileNode {
-        // 找到当前深度的所有条目
-        for entry in entries.iter().filter(|e| e.depth() == depth) {
-            // 获取当前条目的父目录
-            let child_path = entry.path();
-            let child_parent = match child_path.parent() {
-                Some(p) => p,
-                None => continue,
-            };
-
-            // 检查当前条目的父目录是否与父节点的路径相同
-            let parent_path = std::path::Path::new(&parent.path);
-            if child_parent == parent_path {
-                // 构建子节点
-                let mut child_node = FileNode {
-                    name: entry.file_name().to_string_lossy().to_string(),
-                    path: entry.path().display().to_string(),
-                    is_dir: entry.file_type().is_dir(),
-                    size: match entry.metadata() {
-                        Ok(meta) => meta.len(),
-                        Err(_) => 0,
-                    },
-                    modified: match entry.metadata() {
-                        Ok(meta) => match meta.modified() {
-                            Ok(time) => match time.duration_since(UNIX_EPOCH) {
-                                Ok(dur) => dur.as_secs(),
-                                Err(_) => 0,
-                            },
-                            Err(_) => 0,
-                        },
-                        Err(_) => 0,
-                    },
-                    children: Vec::new(),
-                };
-
-                // 如果是目录，递归构建子节点
-                if child_node.is_dir {
-                    child_node = self.build_children_recursive(child_node, entries, depth + 1);
-                }
+    /// 以NDJSON格式输出一个条目，每个条目一行，不需要等待遍历结束
+    fn write_ndjson_entry(&self, out: &mut impl Write, entry: &Entry) -> io::Result<()> {
+        let meta = entry.metadata();
+        let line = NdjsonEntry {
+            path: entry.path().display().to_string(),
+            depth: entry.depth(),
+            kind: gnu_type_name(entry.file_type()),
+            size: meta.map(|meta| meta.size),
+            mtime: meta
+                .and_then(|meta| meta.modified)
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|dur| dur.as_secs()),
+            parent: (entry.depth() > 0)
+                .then(|| entry.path().parent())
+                .flatten()
+                .map(|parent| parent.display().to_string()),
+            target: entry
+                .link_target()
+                .map(|target| target.display().to_string()),
+            error: entry.error().or(entry.metadata_error()),
+        };
 
-                // 添加到父节点的子列表中
-                parent.children.push(child_node);
+        serde_json::to_writer(&mut *out, &line)?;
+        writeln!(out)
+    }
+
+    /// 以GNU tree `-J`兼容的格式写入`out`
+    fn format_gnu_json(&self, out: &mut impl Write, tree: Option<&TreeNode>) -> io::Result<()> {
+        let mut items = Vec::new();
+        let (mut dirs, mut files) = (0, 0);
+        if let Some(tree) = tree {
+            items.push(GnuJsonItem::Entry(Box::new(
+                self.build_gnu_entry(tree, &mut dirs, &mut files),
+            )));
+        }
+        if !self.config.args.noreport {
+            items.push(GnuJsonItem::Report {
+                kind: "report",
+                directories: dirs,
+                files,
+                size: self
+                    .config
+                    .args
+                    .du
+                    .and(tree)
+                    .and_then(|tree| tree.entry.metadata())
+                    .map(|meta| meta.size),
+            });
+        }
+
+        serde_json::to_writer_pretty(&mut *out, &items)?;
+        writeln!(out)
+    }
+
+    /// 构建GNU格式的条目，同时统计目录和文件数量（不含根目录）
+    fn build_gnu_entry(
+        &self,
+        tree: &TreeNode,
+        dirs: &mut usize,
+        files: &mut usize,
+    ) -> GnuJsonEntry {
+        let entry = &tree.entry;
+        let depth = entry.depth();
+        let is_dir = entry.file_type().is_dir();
+        if depth > 0 {
+            if is_dir {
+                *dirs += 1;
+            } else {
+                *files += 1;
             }
         }
 
-        parent
+        let args = &self.config.args;
+        let meta = entry.metadata();
+        GnuJsonEntry {
+            kind: gnu_type_name(entry.file_type()),
+            name: if args.full_path || depth == 0 {
+                entry.path().display().to_string()
+            } else {
+                entry.file_name().to_string_lossy().to_string()
+            },
+            target: entry
+                .link_target()
+                .map(|target| target.display().to_string()),
+            mode: meta
+                .and_then(|meta| meta.mode)
+                .filter(|_| args.perms)
+                .map(|mode| format!("{:04o}", mode & 0o7777)),
+            prot: args.perms.then(|| self.format_perms(entry)),
+            user: args.uid.then(|| self.format_uid(entry)),
+            group: args.gid.then(|| self.format_gid(entry)),
+            size: meta.map(|meta| meta.size).filter(|_| args.size),
+            time: args.mtime.then(|| self.format_mtime(entry)),
+            error: self.dir_note(entry),
+            contents: (is_dir || depth == 0).then(|| {
+                tree.children
+                    .iter()
+                    .map(|child| self.build_gnu_entry(child, dirs, files))
+                    .collect()
+            }),
+        }
+    }
+
+    /// 以JSON格式输出并返回字符串
+    pub fn json_to_string(&self, tree: Option<&TreeNode>, errors: &[WalkError]) -> String {
+        let mut buf = Vec::new();
+        // 写入内存缓冲区不会失败
+        let _ = self.format_json(&mut buf, tree, errors);
+        String::from_utf8_lossy(&buf).into_owned()
     }
+}
 
-    /// 以JSON格式输出
-    pub fn format_json(&self, entries: impl Iterator<Item = DirEntry>) {
-        let entries: Vec<_> = entries.collect();
+/// 文件类型在GNU tree中的名称，用作XML元素名和JSON的`type`
+fn gnu_type_name(kind: FileKind) -> &'static str {
+    match kind {
+        FileKind::Dir => "directory",
+        FileKind::Symlink => "link",
+        FileKind::Socket => "socket",
+        FileKind::Fifo => "fifo",
+        FileKind::BlockDevice => "block",
+        FileKind::CharDevice => "char",
+        FileKind::File | FileKind::Other => "file",
+    }
+}
 
-        // 构建文件树
-        let file_tree = self.build_file_tree(&entries);
+/// 转义XML和HTML中的特殊字符
+fn escape_markup(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
 
-        // 序列化为JSON并输出
-        match serde_json::to_string_pretty(&file_tree) {
-            Ok(json) => println!("{}", json),
-            Err(e) => eprintln!("Error generating JSON: {}", e),
+/// 对URL路径片段进行百分号编码，只保留RFC 3986中的非保留字符
+fn percent_encode(segment: &str) -> String {
+    let mut result = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
         }
     }
+    result
+}
+
+/// 相对于`now`的时间描述，例如"3 days ago"或"in 2 hours"
+fn relative_time(time: SystemTime, now: SystemTime) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ];
+
+    let (secs, future) = match now.duration_since(time) {
+        Ok(age) => (age.as_secs(), false),
+        Err(err) => (err.duration().as_secs(), true),
+    };
+
+    let Some((unit, count)) = UNITS
+        .iter()
+        .map(|(unit, len)| (*unit, secs / len))
+        .find(|(_, count)| *count > 0)
+    else {
+        return "just now".to_string();
+    };
+
+    let plural = if count == 1 { "" } else { "s" };
+    if future {
+        format!("in {} {}{}", count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
 }
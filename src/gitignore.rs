@@ -0,0 +1,145 @@
+//! .gitignore / .ignore 规则支持
+//!
+//! 按照git和ripgrep的约定组合多个来源的忽略规则，优先级从高到低为：
+//! 各级目录中的`.ignore`、各级目录中的`.gitignore`、`.git/info/exclude`、
+//! 全局排除文件（`core.excludesFile`）。同一来源中越深的目录优先级越高，
+//! 并且支持`!pattern`形式的否定规则。
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 单个目录中的忽略文件
+struct DirIgnores {
+    /// `.ignore` 规则
+    ignore: Gitignore,
+    /// `.gitignore` 规则（仅在git仓库内生效）
+    gitignore: Gitignore,
+}
+
+/// 忽略规则过滤器
+///
+/// 每个目录的忽略文件只在第一次访问时读取并缓存，可在多个线程之间共享。
+pub struct IgnoreFilter {
+    /// 遍历起始路径（与遍历结果中的路径前缀一致）
+    start: PathBuf,
+    /// 起始路径的绝对形式，用于与规则文件的根目录比较
+    start_abs: PathBuf,
+    /// 起始路径所在git仓库的根目录
+    repo_root: Option<PathBuf>,
+    /// `.git/info/exclude` 规则
+    exclude: Gitignore,
+    /// 全局排除规则
+    global: Gitignore,
+    /// 每个目录的忽略规则缓存（绝对路径为键）
+    dirs: Mutex<HashMap<PathBuf, Arc<DirIgnores>>>,
+}
+
+impl IgnoreFilter {
+    /// 为指定的遍历起始路径创建过滤器
+    pub fn new(start: &Path) -> Self {
+        let start_abs = std::path::absolute(start).unwrap_or_else(|_| start.to_path_buf());
+        let repo_root = start_abs
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(Path::to_path_buf);
+
+        let (exclude, global) = match &repo_root {
+            Some(root) => (
+                load_rules(root, &[root.join(".git").join("info").join("exclude")]),
+                Gitignore::global().0,
+            ),
+            None => (Gitignore::empty(), Gitignore::empty()),
+        };
+
+        Self {
+            start: start.to_path_buf(),
+            start_abs,
+            repo_root,
+            exclude,
+            global,
+            dirs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 判断路径是否被忽略
+    ///
+    /// `path` 必须是以遍历起始路径为前缀的路径。起始路径本身永远不会被忽略。
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let abs = match path.strip_prefix(&self.start) {
+            Ok(rel) if rel.as_os_str().is_empty() => return false,
+            Ok(rel) => self.start_abs.join(rel),
+            Err(_) => return false,
+        };
+
+        // 收集从直接父目录到规则搜索上界的所有目录（由深到浅）
+        let top = self.repo_root.as_deref().unwrap_or(&self.start_abs);
+        let levels: Vec<Arc<DirIgnores>> = abs
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(top))
+            .map(|dir| self.dir_ignores(dir))
+            .collect();
+
+        // 按来源优先级依次检查，第一个明确的匹配（忽略或白名单）决定结果
+        let sources = [
+            levels
+                .iter()
+                .map(|l| l.ignore.matched(&abs, is_dir))
+                .find(|m| !m.is_none()),
+            levels
+                .iter()
+                .map(|l| l.gitignore.matched(&abs, is_dir))
+                .find(|m| !m.is_none()),
+            Some(self.exclude.matched(&abs, is_dir)),
+            Some(self.global.matched(&abs, is_dir)),
+        ];
+
+        for m in sources.into_iter().flatten() {
+            match m {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        false
+    }
+
+    /// 获取（必要时读取）某个目录中的忽略规则
+    fn dir_ignores(&self, dir: &Path) -> Arc<DirIgnores> {
+        if let Some(cached) = self.dirs.lock().unwrap().get(dir) {
+            return Arc::clone(cached);
+        }
+
+        // 在锁外读取文件，避免阻塞其他线程
+        let loaded = Arc::new(DirIgnores {
+            ignore: load_rules(dir, &[dir.join(".ignore")]),
+            gitignore: if self.repo_root.is_some() {
+                load_rules(dir, &[dir.join(".gitignore")])
+            } else {
+                Gitignore::empty()
+            },
+        });
+
+        Arc::clone(
+            self.dirs
+                .lock()
+                .unwrap()
+                .entry(dir.to_path_buf())
+                .or_insert(loaded),
+        )
+    }
+}
+
+/// 从规则文件构建匹配器，不存在或无法解析的文件会被跳过
+fn load_rules(root: &Path, files: &[PathBuf]) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    for file in files.iter().filter(|f| f.is_file()) {
+        // 单行语法错误不影响其余规则，与git的行为一致
+        let _ = builder.add(file);
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
@@ -3,10 +3,22 @@
 //! 这个库提供了目录遍历、格式化和输出功能。
 
 pub mod config;
+pub mod entry;
 pub mod formatter;
+pub mod gitignore;
+pub mod output;
+pub mod pattern;
+pub mod perms;
+pub mod tree;
 pub mod walker;
 
 // 导出主要类型以便测试
-pub use config::{Args, Config};
+pub use config::{
+    Args, Config, ConfigBuilder, ConfigError, DuMode, JsonFormat, SortKey, TimeField,
+};
+pub use entry::{Entry, FileKind, FileMeta};
 pub use formatter::{FileNode, Formatter};
-pub use walker::Walker;
+pub use output::AtomicFile;
+pub use pattern::Pattern;
+pub use tree::TreeNode;
+pub use walker::{WalkError, Walker};
@@ -1,31 +1,134 @@
-use treecmd::{Config, Formatter, Walker};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use treecmd::{AtomicFile, Config, Formatter, JsonFormat, TreeNode, Walker};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 创建配置实例
-    let config = Config::new()?;
+    let config = match Config::new() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("tree: {}", err);
+            std::process::exit(1);
+        }
+    };
 
-    // 创建目录遍历器
-    let walker = Walker::new(config.clone());
+    // 是否彩色输出由配置决定，而不是由colored根据stdout自行判断，
+    // 这样`-C`在输出到管道或文件时同样生效
+    #[cfg(feature = "color")]
+    colored::control::set_override(config.color_enabled);
 
-    // 执行目录遍历（根据配置选择并行或串行）
-    let sorted_entries = if config.args.threads.is_some() || config.args.progress {
-        // 使用并行处理
-        walker.walk_parallel()
-    } else {
-        // 使用串行处理
-        let entries = walker.walk();
-        walker.sort_entries(entries.collect())
-    };
+    // 创建目录遍历器
+    let mut walker = Walker::new(config.clone());
 
     // 创建输出格式化器
     let mut formatter = Formatter::new(config.clone());
 
-    // 根据配置选择输出格式
-    if config.args.json {
-        formatter.format_json(sorted_entries.into_iter());
+    // --fromfile：从路径列表构建目录树，在打开输出之前读取，失败时不产生输出
+    let listing = match &config.args.fromfile {
+        Some(source) => match read_listing(&walker, source) {
+            Ok(tree) => Some(tree),
+            Err(err) => {
+                eprintln!("tree: {}: {}", source.display(), err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if let Some(path) = &config.args.output {
+        // 写入同一目录下的临时文件，全部成功后再替换目标文件；
+        // 临时文件可能位于遍历的目录中，不显示它
+        let result = AtomicFile::create(path).and_then(|file| {
+            walker.skip_path(file.temp_path());
+            let mut out = BufWriter::new(file);
+            write_output(&config, &walker, listing.as_ref(), &mut formatter, &mut out)?;
+            out.into_inner().map_err(|err| err.into_error())?.commit()
+        });
+        if let Err(err) = result {
+            eprintln!("tree: {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
     } else {
-        formatter.format_tree(sorted_entries.into_iter());
+        // 输出到终端时按行刷新以便立即看到结果，输出到管道或文件时整块写入
+        let stdout = io::stdout();
+        let mut out: Box<dyn Write> = if atty::is(atty::Stream::Stdout) {
+            Box::new(stdout.lock())
+        } else {
+            Box::new(BufWriter::new(stdout.lock()))
+        };
+
+        // 下游关闭管道（例如`tree | head`）时安静退出
+        if let Err(err) = write_output(&config, &walker, listing.as_ref(), &mut formatter, &mut out)
+            .and_then(|()| out.flush())
+        {
+            if err.kind() == io::ErrorKind::BrokenPipe {
+                return Ok(());
+            }
+            eprintln!("tree: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    // 无法读取的路径输出到stderr，并以非零状态退出
+    let errors = walker.errors();
+    for error in &errors {
+        eprintln!("tree: {}: {}", error.path.display(), error.message);
+    }
+    if !errors.is_empty() {
+        std::process::exit(1);
     }
 
     Ok(())
 }
+
+/// 读取路径列表，`-`表示标准输入
+fn read_listing(walker: &Walker, source: &Path) -> io::Result<TreeNode> {
+    if source == Path::new("-") {
+        walker.walk_listing(io::stdin().lock())
+    } else {
+        walker.walk_listing(File::open(source)?)
+    }
+}
+
+/// 按配置的输出格式遍历并写入输出，`listing`为从路径列表构建的目录树
+fn write_output(
+    config: &Config,
+    walker: &Walker,
+    listing: Option<&TreeNode>,
+    formatter: &mut Formatter,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    if let Some(tree) = listing {
+        return if config.args.json {
+            formatter.format_json(out, Some(tree), &[])
+        } else {
+            formatter.format_tree(out, tree)
+        };
+    }
+
+    // 是否使用并行处理
+    let parallel = config.args.threads.is_some() || config.args.progress;
+
+    // NDJSON逐条输出，其余JSON格式需要完整的目录树
+    let whole_json = config.args.json && config.args.json_format != Some(JsonFormat::Ndjson);
+
+    if parallel || whole_json || config.args.du.is_some() {
+        // 并行遍历、JSON输出和--du都需要完整的目录树
+        let tree = walker.walk_tree();
+
+        // 根据配置选择输出格式
+        if config.args.json {
+            formatter.format_json(out, tree.as_ref(), &walker.errors())
+        } else if let Some(tree) = &tree {
+            formatter.format_tree(out, tree)
+        } else {
+            Ok(())
+        }
+    } else {
+        // 串行文本、XML、HTML和NDJSON输出：边遍历边打印
+        formatter.begin_tree(out)?;
+        walker.walk_streaming(|entry, is_last| formatter.write_tree_entry(out, &entry, is_last))?;
+        formatter.end_tree(out)
+    }
+}
@@ -0,0 +1,116 @@
+//! `-I`/`-P`的匹配模式
+//!
+//! 默认与GNU tree一致使用glob语法，一个模式中可以用`|`分隔多个候选，
+//! 例如`node_modules|*.o`；`--regex`时每个模式是一个正则表达式。
+//! 选项可以重复指定，匹配任意一个模式即算匹配。
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::{RegexSet, RegexSetBuilder};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+/// 编译后的一组模式
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    matcher: Matcher,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Glob(GlobSet),
+    Regex(RegexSet),
+}
+
+/// 模式无法编译
+#[derive(Debug)]
+pub struct PatternError {
+    /// 出错的模式
+    pub pattern: String,
+    /// glob或正则表达式的解析错误
+    pub source: Box<dyn Error + Send + Sync>,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}': {}", self.pattern, self.source)
+    }
+}
+
+impl Error for PatternError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+impl Pattern {
+    /// 编译glob模式，每个模式按`|`拆分为多个候选
+    ///
+    /// `*`和`?`不匹配路径分隔符，需要跨越目录时使用`**`。
+    pub fn glob<S: AsRef<str>>(patterns: &[S], ignore_case: bool) -> Result<Self, PatternError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            for alternative in pattern.as_ref().split('|').filter(|alt| !alt.is_empty()) {
+                let glob = GlobBuilder::new(alternative)
+                    .case_insensitive(ignore_case)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| PatternError {
+                        pattern: pattern.as_ref().to_string(),
+                        source: Box::new(err),
+                    })?;
+                builder.add(glob);
+            }
+        }
+
+        let set = builder.build().map_err(|err| PatternError {
+            pattern: patterns
+                .iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .join("|"),
+            source: Box::new(err),
+        })?;
+        Ok(Self {
+            matcher: Matcher::Glob(set),
+        })
+    }
+
+    /// 编译正则表达式，与`--regex`之前的行为一致：在名称中查找匹配
+    pub fn regex<S: AsRef<str>>(patterns: &[S], ignore_case: bool) -> Result<Self, PatternError> {
+        // 逐个编译以便报告出错的是哪个模式
+        for pattern in patterns {
+            regex::RegexBuilder::new(pattern.as_ref())
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|err| PatternError {
+                    pattern: pattern.as_ref().to_string(),
+                    source: Box::new(err),
+                })?;
+        }
+
+        let set = RegexSetBuilder::new(patterns)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| PatternError {
+                pattern: patterns
+                    .iter()
+                    .map(AsRef::as_ref)
+                    .collect::<Vec<_>>()
+                    .join("|"),
+                source: Box::new(err),
+            })?;
+        Ok(Self {
+            matcher: Matcher::Regex(set),
+        })
+    }
+
+    /// 名称或相对路径是否匹配任意一个模式
+    pub fn is_match(&self, candidate: impl AsRef<Path>) -> bool {
+        let candidate = candidate.as_ref();
+        match &self.matcher {
+            Matcher::Glob(set) => set.is_match(candidate),
+            Matcher::Regex(set) => set.is_match(&candidate.to_string_lossy()),
+        }
+    }
+}
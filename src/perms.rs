@@ -0,0 +1,156 @@
+//! 文件权限与所有者信息
+//!
+//! 提供Unix模式位到`ls -l`风格字符串的转换，以及带缓存的用户名/组名解析。
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// 将Unix模式位（包含文件类型位）格式化为`drwxr-xr-x`形式的字符串
+///
+/// 支持setuid/setgid/sticky位以及套接字、FIFO、块设备和字符设备的类型字符。
+pub fn format_mode(mode: u32) -> String {
+    const S_IFMT: u32 = 0o170000;
+    const S_IFSOCK: u32 = 0o140000;
+    const S_IFLNK: u32 = 0o120000;
+    const S_IFREG: u32 = 0o100000;
+    const S_IFBLK: u32 = 0o060000;
+    const S_IFDIR: u32 = 0o040000;
+    const S_IFCHR: u32 = 0o020000;
+    const S_IFIFO: u32 = 0o010000;
+
+    let type_char = match mode & S_IFMT {
+        S_IFDIR => 'd',
+        S_IFLNK => 'l',
+        S_IFSOCK => 's',
+        S_IFIFO => 'p',
+        S_IFBLK => 'b',
+        S_IFCHR => 'c',
+        S_IFREG => '-',
+        _ => '?',
+    };
+
+    // 执行位与特殊位组合：特殊位存在时使用小写（有执行权限）或大写（无执行权限）
+    let exec_char = |exec: bool, special: bool, set: char| match (exec, special) {
+        (true, true) => set,
+        (false, true) => set.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+    let bit = |mask: u32, c: char| if mode & mask != 0 { c } else { '-' };
+
+    let mut result = String::with_capacity(10);
+    result.push(type_char);
+    result.push(bit(0o400, 'r'));
+    result.push(bit(0o200, 'w'));
+    result.push(exec_char(mode & 0o100 != 0, mode & 0o4000 != 0, 's'));
+    result.push(bit(0o040, 'r'));
+    result.push(bit(0o020, 'w'));
+    result.push(exec_char(mode & 0o010 != 0, mode & 0o2000 != 0, 's'));
+    result.push(bit(0o004, 'r'));
+    result.push(bit(0o002, 'w'));
+    result.push(exec_char(mode & 0o001 != 0, mode & 0o1000 != 0, 't'));
+    result
+}
+
+/// 用户名/组名缓存
+///
+/// 大型目录树中同一个UID/GID会反复出现，缓存可以避免重复查询系统数据库。
+/// 无法解析的ID会以数字形式返回（同样会被缓存）。
+#[derive(Debug, Default)]
+pub struct OwnerCache {
+    users: RefCell<HashMap<u32, String>>,
+    groups: RefCell<HashMap<u32, String>>,
+}
+
+impl OwnerCache {
+    /// 创建空缓存
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取UID对应的用户名，无法解析时返回数字UID
+    pub fn user_name(&self, uid: u32) -> String {
+        self.users
+            .borrow_mut()
+            .entry(uid)
+            .or_insert_with(|| lookup_user(uid).unwrap_or_else(|| uid.to_string()))
+            .clone()
+    }
+
+    /// 获取GID对应的组名，无法解析时返回数字GID
+    pub fn group_name(&self, gid: u32) -> String {
+        self.groups
+            .borrow_mut()
+            .entry(gid)
+            .or_insert_with(|| lookup_group(gid).unwrap_or_else(|| gid.to_string()))
+            .clone()
+    }
+}
+
+/// 查询缓冲区上限，防止异常的ERANGE循环
+#[cfg(unix)]
+const MAX_LOOKUP_BUF: usize = 1 << 20;
+
+/// 通过getpwuid_r查询用户名
+#[cfg(unix)]
+fn lookup_user(uid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        // SAFETY: passwd是纯数据结构，全零是合法的初始值
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        // SAFETY: 所有指针都指向有效的本地缓冲区，长度与缓冲区一致
+        let rc =
+            unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+
+        if rc == libc::ERANGE && buf.len() < MAX_LOOKUP_BUF {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if rc != 0 || result.is_null() || pwd.pw_name.is_null() {
+            return None;
+        }
+
+        // SAFETY: 查询成功时pw_name指向buf中以NUL结尾的字符串
+        let name = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+        return Some(name.to_string_lossy().into_owned());
+    }
+}
+
+/// 通过getgrgid_r查询组名
+#[cfg(unix)]
+fn lookup_group(gid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        // SAFETY: group是纯数据结构，全零是合法的初始值
+        let mut grp: libc::group = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::group = std::ptr::null_mut();
+        // SAFETY: 所有指针都指向有效的本地缓冲区，长度与缓冲区一致
+        let rc =
+            unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+
+        if rc == libc::ERANGE && buf.len() < MAX_LOOKUP_BUF {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if rc != 0 || result.is_null() || grp.gr_name.is_null() {
+            return None;
+        }
+
+        // SAFETY: 查询成功时gr_name指向buf中以NUL结尾的字符串
+        let name = unsafe { std::ffi::CStr::from_ptr(grp.gr_name) };
+        return Some(name.to_string_lossy().into_owned());
+    }
+}
+
+/// 非Unix平台没有用户数据库，始终回退到数字ID
+#[cfg(not(unix))]
+fn lookup_user(_uid: u32) -> Option<String> {
+    None
+}
+
+/// 非Unix平台没有组数据库，始终回退到数字ID
+#[cfg(not(unix))]
+fn lookup_group(_gid: u32) -> Option<String> {
+    None
+}
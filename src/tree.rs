@@ -0,0 +1,211 @@
+//! 目录树结构
+//!
+//! 与文件系统无关的目录树模型：`Walker`遍历文件系统得到它，
+//! 调用方也可以从任意路径列表（归档、清单、远程列表等）构建它，
+//! 所有输出格式都基于它完成渲染，整体为线性时间。
+
+use crate::entry::{Entry, FileKind, FileMeta};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+/// 目录树节点：条目及其按显示顺序排列的子节点
+#[derive(Debug, Clone)]
+pub struct TreeNode {
+    pub entry: Entry,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// 创建没有子节点的节点
+    pub fn new(entry: Entry) -> Self {
+        Self {
+            entry,
+            children: Vec::new(),
+        }
+    }
+
+    /// 从按深度优先顺序排列的条目列表构建目录树
+    ///
+    /// 第一个条目必须是深度为0的根条目，否则返回None。
+    /// 父目录不在列表中的条目（例如父目录被过滤掉）会被丢弃，与`Walker::sort_entries`一致。
+    pub fn from_entries(entries: impl IntoIterator<Item = Entry>) -> Option<Self> {
+        let mut entries = entries.into_iter();
+        let root = entries.next().filter(|e| e.depth() == 0)?;
+
+        // 栈中保存当前路径上尚未完成的节点，stack[i]的深度为i
+        let mut stack = vec![TreeNode::new(root)];
+
+        for entry in entries {
+            let depth = entry.depth();
+            if depth == 0 || depth > stack.len() {
+                continue;
+            }
+
+            // 弹出比新条目更深或同级的节点，挂到各自的父节点上
+            while stack.len() > depth {
+                let node = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(node);
+            }
+
+            // 栈顶必须是新条目的父目录
+            let parent = &stack.last().unwrap().entry;
+            if !parent.file_type().is_dir() || entry.path().parent() != Some(parent.path()) {
+                continue;
+            }
+
+            stack.push(TreeNode::new(entry));
+        }
+
+        while stack.len() > 1 {
+            let node = stack.pop().unwrap();
+            stack.last_mut().unwrap().children.push(node);
+        }
+
+        stack.pop()
+    }
+
+    /// 从任意路径列表构建目录树
+    ///
+    /// 路径相对于`root`解释，缺失的中间目录会自动补全。以路径分隔符结尾的路径
+    /// 以及含有子路径的路径视为目录，其余视为普通文件。子条目保持首次出现的顺序。
+    pub fn from_paths<I, P>(root: impl Into<PathBuf>, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        Self::from_path_entries(
+            root,
+            paths.into_iter().map(|path| {
+                let path = path.as_ref();
+                let is_dir = path
+                    .as_os_str()
+                    .to_string_lossy()
+                    .ends_with(std::path::is_separator);
+                let kind = if is_dir {
+                    FileKind::Dir
+                } else {
+                    FileKind::File
+                };
+                (path.to_path_buf(), kind, None)
+            }),
+        )
+    }
+
+    /// 从带有文件类型和元数据的路径列表构建目录树
+    ///
+    /// 与`from_paths`相同，但每个路径可以指定文件类型和元数据。
+    /// 同一路径重复出现时以最后一次为准；已经含有子条目的路径始终是目录。
+    pub fn from_path_entries<I, P>(root: impl Into<PathBuf>, items: I) -> Self
+    where
+        I: IntoIterator<Item = (P, FileKind, Option<FileMeta>)>,
+        P: AsRef<Path>,
+    {
+        /// 构建过程中的节点：条目、子节点下标、按文件名索引的子节点
+        struct Slot {
+            entry: Option<Entry>,
+            children: Vec<usize>,
+            index: HashMap<OsString, usize>,
+        }
+
+        let mut slots = vec![Slot {
+            entry: Some(Entry::new(root, 0, FileKind::Dir)),
+            children: Vec::new(),
+            index: HashMap::new(),
+        }];
+
+        for (path, kind, meta) in items {
+            // 只保留普通路径组成部分，忽略根、前缀、"."和".."
+            let names: Vec<&std::ffi::OsStr> = path
+                .as_ref()
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name),
+                    _ => None,
+                })
+                .collect();
+
+            let mut current = 0;
+            for (i, name) in names.iter().enumerate() {
+                let is_leaf = i + 1 == names.len();
+
+                let child = match slots[current].index.get(*name) {
+                    Some(&child) => child,
+                    None => {
+                        let parent = slots[current].entry.as_ref().unwrap();
+                        let entry =
+                            Entry::new(parent.path().join(name), parent.depth() + 1, FileKind::Dir);
+                        slots.push(Slot {
+                            entry: Some(entry),
+                            children: Vec::new(),
+                            index: HashMap::new(),
+                        });
+                        let child = slots.len() - 1;
+                        slots[current].children.push(child);
+                        slots[current].index.insert(name.to_os_string(), child);
+                        child
+                    }
+                };
+
+                let has_children = !slots[child].children.is_empty();
+                let entry = slots[child].entry.as_mut().unwrap();
+                if is_leaf {
+                    entry.set_file_type(if has_children { FileKind::Dir } else { kind });
+                    entry.set_metadata(meta.clone());
+                } else {
+                    entry.set_file_type(FileKind::Dir);
+                }
+
+                current = child;
+            }
+
+            // 空路径或"."表示根目录本身
+            if names.is_empty() && meta.is_some() {
+                slots[0].entry.as_mut().unwrap().set_metadata(meta);
+            }
+        }
+
+        fn assemble(slots: &mut [Slot], index: usize) -> TreeNode {
+            let children = std::mem::take(&mut slots[index].children);
+            TreeNode {
+                entry: slots[index].entry.take().unwrap(),
+                children: children
+                    .into_iter()
+                    .map(|child| assemble(slots, child))
+                    .collect(),
+            }
+        }
+
+        assemble(&mut slots, 0)
+    }
+
+    /// 按深度优先顺序访问所有节点（包括根节点）
+    ///
+    /// 回调参数为条目以及它是否是父节点的最后一个子节点，根节点视为最后一个。
+    pub fn visit(&self, visit: &mut impl FnMut(&Entry, bool)) {
+        self.visit_inner(true, visit);
+    }
+
+    fn visit_inner(&self, is_last: bool, visit: &mut impl FnMut(&Entry, bool)) {
+        visit(&self.entry, is_last);
+
+        let count = self.children.len();
+        for (index, child) in self.children.iter().enumerate() {
+            child.visit_inner(index + 1 == count, visit);
+        }
+    }
+
+    /// 按深度优先顺序展开为条目列表
+    pub fn into_entries(self) -> Vec<Entry> {
+        let mut result = Vec::new();
+        self.flatten_into(&mut result);
+        result
+    }
+
+    fn flatten_into(self, result: &mut Vec<Entry>) {
+        result.push(self.entry);
+        for child in self.children {
+            child.flatten_into(result);
+        }
+    }
+}
@@ -1,18 +1,77 @@
-use crate::config::Config;
+use crate::config::{Config, DuMode, SortKey};
+use crate::entry::{Entry, FileKind, FileMeta, walkdir_error_message};
+use crate::gitignore::IgnoreFilter;
+use crate::tree::TreeNode;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
-use walkdir::{DirEntry, WalkDir};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+use walkdir::WalkDir;
+
+/// 文件的唯一标识：设备ID和inode编号
+type FileId = (u64, u64);
+
+/// 遍历过程中无法读取的路径
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub message: String,
+}
 
 /// 目录遍历引擎
 pub struct Walker {
     config: Config,
+    ignore_filter: Option<IgnoreFilter>,
+    // 并行遍历使用的线程池，未指定线程数时使用rayon的全局线程池
+    pool: Option<rayon::ThreadPool>,
+    // 遍历过程中遇到的错误
+    errors: Mutex<Vec<WalkError>>,
+    // 不显示的文件（规范化的路径），例如`-o`正在写入的临时文件
+    skip_path: Option<PathBuf>,
 }
 
 impl Walker {
     /// 创建新的遍历器
     pub fn new(config: Config) -> Self {
-        Self { config }
+        let ignore_filter = if config.args.gitignore {
+            let start_path = config
+                .args
+                .path
+                .as_deref()
+                .unwrap_or_else(|| Path::new("."));
+            Some(IgnoreFilter::new(start_path))
+        } else {
+            None
+        };
+
+        let pool = config.args.threads.and_then(|threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .ok()
+        });
+
+        Self {
+            config,
+            ignore_filter,
+            pool,
+            errors: Mutex::new(Vec::new()),
+            skip_path: None,
+        }
+    }
+
+    /// 遍历时跳过该文件，例如位于遍历目录中的输出临时文件
+    pub fn skip_path(&mut self, path: &Path) {
+        self.skip_path = std::fs::canonicalize(path).ok();
     }
 
     /// 获取起始路径
@@ -24,8 +83,33 @@ impl Walker {
             .unwrap_or_else(|| Path::new("."))
     }
 
+    /// 记录无法读取的路径
+    fn record_error(&self, path: &Path, message: &str) {
+        self.errors.lock().unwrap().push(WalkError {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        });
+    }
+
+    /// 返回遍历过程中遇到的所有错误（按路径排序并去重）
+    pub fn errors(&self) -> Vec<WalkError> {
+        let mut errors = self.errors.lock().unwrap().clone();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        errors.dedup();
+        errors
+    }
+
+    /// 检查条目是否被.gitignore/.ignore规则忽略
+    /// 被忽略的目录会被整体剪枝，不再进入其中
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        match &self.ignore_filter {
+            Some(filter) => filter.is_ignored(path, is_dir),
+            None => false,
+        }
+    }
+
     /// 过滤目录条目
-    fn filter_entry(&self, entry: &DirEntry) -> bool {
+    fn filter_entry(&self, entry: &Entry) -> bool {
         // 根目录总是保留
         if entry.depth() == 0 {
             return true;
@@ -53,29 +137,39 @@ impl Walker {
             }
         }
 
+        // 跳过指定的文件，先比较文件名以免对每个条目规范化路径
+        if let Some(skip) = &self.skip_path
+            && skip.file_name() == Some(entry.file_name())
+            && std::fs::canonicalize(entry.path()).is_ok_and(|path| &path == skip)
+        {
+            return false;
+        }
+
         // 检查是否仅显示目录
         if self.config.args.dirs_only && !entry.file_type().is_dir() {
             return false;
         }
 
-        // 检查排除模式
-        if let Some(exclude_regex) = &self.config.exclude_regex
-            && let Some(file_name) = entry.file_name().to_str()
-            && exclude_regex.is_match(file_name)
+        // 检查排除和包含模式，--match-path时匹配相对于起始路径的路径
+        let candidate = if self.config.args.match_path {
+            entry
+                .path()
+                .strip_prefix(self.get_start_path())
+                .unwrap_or(entry.path())
+        } else {
+            Path::new(entry.file_name())
+        };
+        if let Some(exclude) = &self.config.exclude_pattern
+            && exclude.is_match(candidate)
         {
             return false;
         }
-
-        // 检查包含模式
-        if let Some(include_regex) = &self.config.include_regex {
-            if let Some(file_name) = entry.file_name().to_str() {
-                if !include_regex.is_match(file_name) {
-                    return false;
-                }
-            } else {
-                // 如果文件名无法转换为字符串，且有包含模式，则排除
-                return false;
-            }
+        // 与GNU tree一致，-P只作用于文件，目录总是保留以便显示其中匹配的文件
+        if let Some(include) = &self.config.include_pattern
+            && !entry.file_type().is_dir()
+            && !include.is_match(candidate)
+        {
+            return false;
         }
 
         true
@@ -83,7 +177,7 @@ impl Walker {
 
     /// 遍历目录并返回符合条件的条目
     /// 如果启用进度显示，会返回带有进度条的迭代器
-    pub fn walk(&self) -> Box<dyn Iterator<Item = DirEntry> + '_> {
+    pub fn walk(&self) -> Box<dyn Iterator<Item = Entry> + '_> {
         let start_path = self.get_start_path();
         let mut walkdir = WalkDir::new(start_path);
 
@@ -99,10 +193,38 @@ impl Walker {
         }
 
         // 执行遍历并过滤
-        let iter = walkdir
+        let mut walkdir_iter = walkdir
             .into_iter()
-            .filter_map(Result::ok)
-            .filter(|entry| self.filter_entry(entry));
+            .filter_entry(|entry| !self.is_ignored(entry.path(), entry.file_type().is_dir()))
+            .peekable();
+
+        let iter = std::iter::from_fn(move || {
+            loop {
+                match walkdir_iter.next()? {
+                    Ok(dirent) => {
+                        let mut entry = Entry::from_walkdir(&dirent);
+
+                        // walkdir在产出目录条目之后才报告打开该目录时的错误
+                        if let Some(Err(err)) = walkdir_iter.peek()
+                            && err.path() == Some(dirent.path())
+                        {
+                            let message = walkdir_error_message(err);
+                            self.record_error(dirent.path(), &message);
+                            entry.set_error(message);
+                            walkdir_iter.next();
+                        }
+
+                        return Some(entry);
+                    }
+                    Err(err) => {
+                        let path = err.path().unwrap_or(start_path);
+                        self.record_error(path, &walkdir_error_message(&err));
+                    }
+                }
+            }
+        })
+        .filter(|entry| self.filter_entry(entry))
+        .inspect(|entry| self.record_metadata_error(entry));
 
         // 如果启用进度显示，包装进度条
         if self.config.args.progress {
@@ -124,7 +246,7 @@ impl Walker {
     }
 
     /// 对条目进行排序，保持深度优先顺序，只对同一目录下的条目排序
-    pub fn sort_entries(&self, entries: Vec<DirEntry>) -> Vec<DirEntry> {
+    pub fn sort_entries(&self, entries: Vec<Entry>) -> Vec<Entry> {
         // 如果没有条目，直接返回空列表
         if entries.is_empty() {
             return Vec::new();
@@ -173,43 +295,14 @@ impl Walker {
     /// 递归处理目录组
     fn process_dir_groups(
         &self,
-        dir_groups: &std::collections::HashMap<std::path::PathBuf, Vec<DirEntry>>,
-        dir: &DirEntry,
-        result: &mut Vec<DirEntry>,
+        dir_groups: &std::collections::HashMap<std::path::PathBuf, Vec<Entry>>,
+        dir: &Entry,
+        result: &mut Vec<Entry>,
     ) {
         // 检查当前目录是否有子目录
         if let Some(children) = dir_groups.get(dir.path()) {
-            // 复制子目录列表并排序
-            let mut sorted_children = children.clone();
-
             // 对当前目录的子条目进行排序
-            sorted_children.sort_by(|a, b| {
-                // 目录排在文件前面
-                let a_is_dir = a.file_type().is_dir();
-                let b_is_dir = b.file_type().is_dir();
-
-                if a_is_dir != b_is_dir {
-                    return if a_is_dir {
-                        std::cmp::Ordering::Less
-                    } else {
-                        std::cmp::Ordering::Greater
-                    };
-                }
-
-                // 对于同一类型的条目，按指定顺序排序
-                match self.config.args.sort.as_deref() {
-                    Some("time") => {
-                        // 按修改时间排序，最新的在前
-                        let a_meta = a.metadata().unwrap();
-                        let b_meta = b.metadata().unwrap();
-                        b_meta.modified().unwrap().cmp(&a_meta.modified().unwrap())
-                    }
-                    Some(_) | None => {
-                        // 按名称排序（默认）
-                        a.file_name().cmp(b.file_name())
-                    }
-                }
-            });
+            let sorted_children = self.sort_children(children.clone(), |entry| entry);
 
             // 添加排序后的子条目到结果中
             for child in sorted_children {
@@ -223,9 +316,188 @@ impl Walker {
         }
     }
 
+    /// 显示的条目无法读取元数据时，与无法打开的目录一样报告错误
+    fn record_metadata_error(&self, entry: &Entry) {
+        if let Some(message) = entry.metadata_error() {
+            self.record_error(entry.path(), message);
+        }
+    }
+
+    /// 对同一目录下的条目排序
+    ///
+    /// 名称的排序键在排序前为每个条目计算一次，而不是在每次比较时重新计算。
+    /// 串行、并行和路径列表三种遍历方式都使用这里的顺序。
+    /// 只对一个目录的条目串行排序，不启动rayon线程池，并行遍历已经按目录并行。
+    fn sort_children<T, F>(&self, items: Vec<T>, entry: F) -> Vec<T>
+    where
+        F: Fn(&T) -> &Entry,
+    {
+        // 不排序时用不到名称排序键
+        let ignore_case = self.config.args.ignore_case;
+        let unsorted = self.config.args.sort == Some(SortKey::None);
+        let mut keyed: Vec<(String, T)> = items
+            .into_iter()
+            .map(|item| {
+                let key = if unsorted {
+                    String::new()
+                } else {
+                    collation_key(entry(&item).file_name(), ignore_case)
+                };
+                (key, item)
+            })
+            .collect();
+
+        keyed.sort_by(|(a_key, a), (b_key, b)| {
+            self.compare_entries(entry(a), a_key, entry(b), b_key)
+        });
+        keyed.into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// 比较同一目录下的两个条目，决定它们的显示顺序，`a_key`和`b_key`是名称的排序键
+    fn compare_entries(&self, a: &Entry, a_key: &str, b: &Entry, b_key: &str) -> Ordering {
+        let args = &self.config.args;
+
+        // --dirsfirst/--filesfirst先按类型分组，默认目录和文件混合排序
+        let a_is_dir = a.file_type().is_dir();
+        let b_is_dir = b.file_type().is_dir();
+        if a_is_dir != b_is_dir && (args.dirsfirst || args.filesfirst) {
+            return if a_is_dir == args.dirsfirst {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+        }
+
+        // 不排序时保持目录中的顺序（排序是稳定的）
+        let key = args.sort.unwrap_or_default();
+        if key == SortKey::None {
+            return Ordering::Equal;
+        }
+
+        // 缺少排序所需元数据的条目总是排在最后，不受-r影响
+        let has_sort_value = |entry: &Entry| {
+            let meta = entry.metadata();
+            match key {
+                SortKey::Size => meta.is_some(),
+                SortKey::Mtime => meta.is_some_and(|m| m.modified.is_some()),
+                SortKey::Ctime => meta.is_some_and(|m| m.changed.is_some()),
+                _ => true,
+            }
+        };
+        let missing_last = has_sort_value(b).cmp(&has_sort_value(a));
+
+        // 按指定顺序排序，相同时按名称
+        let by_name = || {
+            a_key
+                .cmp(b_key)
+                .then_with(|| a.file_name().cmp(b.file_name()))
+        };
+        let ordering = match key {
+            SortKey::Name | SortKey::None => by_name(),
+            SortKey::Version => {
+                version_cmp(a_key, b_key).then_with(|| a.file_name().cmp(b.file_name()))
+            }
+            // 大的在前
+            SortKey::Size => {
+                let a_size = a.metadata().map(|m| m.size);
+                let b_size = b.metadata().map(|m| m.size);
+                b_size.cmp(&a_size).then_with(by_name)
+            }
+            // 最新的在前
+            SortKey::Mtime => {
+                let a_time = a.metadata().and_then(|m| m.modified);
+                let b_time = b.metadata().and_then(|m| m.modified);
+                b_time.cmp(&a_time).then_with(by_name)
+            }
+            SortKey::Ctime => {
+                let a_time = a.metadata().and_then(|m| m.changed);
+                let b_time = b.metadata().and_then(|m| m.changed);
+                b_time.cmp(&a_time).then_with(by_name)
+            }
+            // 没有扩展名的排在前面
+            SortKey::Extension => Path::new(a.file_name())
+                .extension()
+                .cmp(&Path::new(b.file_name()).extension())
+                .then_with(by_name),
+        };
+
+        missing_last.then(if args.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        })
+    }
+
+    /// 遍历目录并返回完整的目录树
+    ///
+    /// 指定了`--threads`或`--progress`时使用并行遍历，否则使用串行遍历。
+    /// 起始路径无法读取时返回None，错误可通过`errors`获取。
+    pub fn walk_tree(&self) -> Option<TreeNode> {
+        let mut tree = if self.config.args.threads.is_some() || self.config.args.progress {
+            self.walk_parallel_tree()
+        } else {
+            let mut entries = Vec::new();
+            let _ = self.walk_streaming(|entry, _| {
+                entries.push(entry);
+                Ok::<(), Infallible>(())
+            });
+            TreeNode::from_entries(entries)
+        };
+
+        if let (Some(mode), Some(tree)) = (self.config.args.du, tree.as_mut()) {
+            self.aggregate_sizes(tree, mode);
+        }
+        tree
+    }
+
+    /// `--du`：把每个条目的大小替换为它及其下所有内容的总大小，返回该总大小
+    ///
+    /// 总大小不受过滤和深度限制影响：没有显示的子条目（隐藏、被过滤、超出深度或
+    /// `--filelimit`）直接从文件系统统计，显示的子条目递归汇总，每个文件只读取一次。
+    /// 按大小排序时，每个目录的子条目在汇总后按总大小重新排序。
+    fn aggregate_sizes(&self, node: &mut TreeNode, mode: DuMode) -> u64 {
+        let mut total = node.entry.metadata().map_or(0, |meta| mode.size_of(meta));
+
+        if node.entry.file_type().is_dir() && !node.entry.is_recursive_link() {
+            let mut shown: HashMap<OsString, &mut TreeNode> = node
+                .children
+                .iter_mut()
+                .map(|child| (child.entry.file_name().to_os_string(), child))
+                .collect();
+
+            // 遍历时已经无法打开的目录已经报告过错误
+            if node.entry.error().is_none() {
+                for child in self.read_dir_for_du(node.entry.path()) {
+                    total += match shown.remove(&child.file_name()) {
+                        Some(child) => self.aggregate_sizes(child, mode),
+                        None => self.disk_usage(&child.path(), mode),
+                    };
+                }
+            }
+
+            // 遍历时按条目自身的大小排序，汇总之后按总大小重新排序
+            if self.config.args.sort == Some(SortKey::Size) {
+                let children = std::mem::take(&mut node.children);
+                node.children = self.sort_children(children, |child| &child.entry);
+            }
+        }
+
+        node.entry.set_size(total);
+        total
+    }
+
     /// 并行遍历目录并返回排序后的条目
-    /// 使用并行处理来加速大目录的遍历和排序
-    pub fn walk_parallel(&self) -> Vec<DirEntry> {
+    ///
+    /// 每个目录的读取、过滤和排序都作为独立任务提交到rayon的工作窃取线程池，
+    /// 子目录之间并发处理。线程数由`--threads`指定，结果顺序与`sort_entries`一致。
+    pub fn walk_parallel(&self) -> Vec<Entry> {
+        self.walk_parallel_tree()
+            .map(TreeNode::into_entries)
+            .unwrap_or_default()
+    }
+
+    /// 并行遍历目录并返回目录树
+    fn walk_parallel_tree(&self) -> Option<TreeNode> {
         let start_path = self.get_start_path();
 
         // 创建进度条（如果启用进度显示）
@@ -243,116 +515,441 @@ impl Walker {
             None
         };
 
-        // 第一步：收集所有条目（串行，因为walkdir本身不是并行的）
-        let all_entries: Vec<DirEntry> = WalkDir::new(start_path)
-            .max_depth(self.config.depth)
-            .follow_links(self.config.args.follow_links)
-            .same_file_system(self.config.args.samefilesystem)
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|entry| self.filter_entry(entry))
-            .inspect(|_| {
-                // 更新进度条
-                if let Some(ref pb) = pb {
-                    pb.inc(1);
-                }
-            })
-            .collect();
+        let root = match Entry::from_root(start_path) {
+            Ok(root) => root,
+            Err(err) => {
+                self.record_error(start_path, &err.to_string());
+                return None;
+            }
+        };
+        let root_device = root.device();
+
+        let walk = || self.walk_subtree(root, root_device, &[], pb.as_ref());
+        let tree = match &self.pool {
+            Some(pool) => pool.install(walk),
+            None => walk(),
+        };
 
         // 完成进度条
         if let Some(ref pb) = pb {
-            pb.finish_with_message(format!("完成，共 {} 个条目", all_entries.len()));
+            pb.finish_with_message(format!("完成，共 {} 个条目", pb.position()));
         }
 
-        if all_entries.is_empty() {
-            return Vec::new();
+        Some(tree)
+    }
+
+    /// 递归遍历子树，子目录作为并行任务处理
+    ///
+    /// `ancestors`为当前路径上各级目录的唯一标识，用于检测跟随链接时的循环。
+    fn walk_subtree(
+        &self,
+        mut entry: Entry,
+        root_device: Option<u64>,
+        ancestors: &[FileId],
+        pb: Option<&ProgressBar>,
+    ) -> TreeNode {
+        if let Some(pb) = pb {
+            pb.inc(1);
         }
 
-        // 第二步：使用并行处理进行排序
-        self.parallel_sort_entries(all_entries)
+        let children = self.load_children(&mut entry, root_device, ancestors);
+        let ancestors: Vec<FileId> = ancestors.iter().copied().chain(entry.file_id()).collect();
+        let children = children
+            .into_par_iter()
+            .map(|child| self.walk_subtree(child, root_device, &ancestors, pb))
+            .collect();
+
+        TreeNode { entry, children }
     }
 
-    /// 并行排序条目 - 使用rayon加速排序过程
-    fn parallel_sort_entries(&self, entries: Vec<DirEntry>) -> Vec<DirEntry> {
-        if entries.is_empty() {
-            return Vec::new();
+    /// 流式遍历目录树，按显示顺序逐个回调条目
+    ///
+    /// 每次只读取并排序一个目录的子条目，回调参数为条目本身以及它是否是父目录中的
+    /// 最后一个子条目。输出无需等待整个遍历结束，内存占用只与当前路径上各目录的宽度有关。
+    /// 回调返回错误时（例如输出管道已关闭）立即停止遍历并返回该错误。
+    pub fn walk_streaming<E>(
+        &self,
+        mut visit: impl FnMut(Entry, bool) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let start_path = self.get_start_path();
+
+        let root = match Entry::from_root(start_path) {
+            Ok(root) => root,
+            Err(err) => {
+                self.record_error(start_path, &err.to_string());
+                return Ok(());
+            }
+        };
+        let root_device = root.device();
+
+        self.stream_subtree(root, true, root_device, &mut Vec::new(), &mut visit)
+    }
+
+    /// 递归地流式输出子树
+    fn stream_subtree<E>(
+        &self,
+        mut entry: Entry,
+        is_last: bool,
+        root_device: Option<u64>,
+        ancestors: &mut Vec<FileId>,
+        visit: &mut impl FnMut(Entry, bool) -> Result<(), E>,
+    ) -> Result<(), E> {
+        // 先读取子条目，这样打开目录失败的错误可以在输出目录本身时标记出来
+        let children = self.load_children(&mut entry, root_device, ancestors);
+        let file_id = entry.file_id();
+        visit(entry, is_last)?;
+
+        ancestors.extend(file_id);
+        let count = children.len();
+        let mut result = Ok(());
+        for (index, child) in children.into_iter().enumerate() {
+            result = self.stream_subtree(child, index + 1 == count, root_device, ancestors, visit);
+            if result.is_err() {
+                break;
+            }
+        }
+        if file_id.is_some() {
+            ancestors.pop();
         }
 
-        // 找到根目录
-        let root_opt = entries.iter().find(|e| e.depth() == 0);
-        if root_opt.is_none() {
-            return entries;
+        result
+    }
+
+    /// 从路径列表构建目录树，而不是遍历文件系统
+    ///
+    /// 输入中每行一个路径；含有NUL字符时改为按NUL分隔（例如`git ls-files -z`）。
+    /// 路径后可以跟以制表符分隔的大小（字节）和修改时间（UNIX时间戳，秒）两列。
+    /// 树根为起始路径，过滤、排序和深度限制与遍历文件系统时一致。
+    pub fn walk_listing(&self, mut input: impl Read) -> io::Result<TreeNode> {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+
+        // 按字节拆分，不是UTF-8的文件名原样保留
+        let separator = if data.contains(&0) { 0 } else { b'\n' };
+        let items = data
+            .split(|&byte| byte == separator)
+            .map(|record| record.strip_suffix(b"\r").unwrap_or(record))
+            .filter(|record| !record.is_empty())
+            .map(parse_listing_record);
+
+        let mut tree = TreeNode::from_path_entries(self.get_start_path(), items);
+
+        // 在过滤之前汇总大小，总大小包含不显示的条目
+        if let Some(mode) = self.config.args.du {
+            aggregate_listing_sizes(&mut tree, mode);
         }
-        let root = root_opt.unwrap().clone();
+        Ok(self.prune_listing(tree))
+    }
 
-        // 按父目录分组
-        let mut dir_groups: std::collections::HashMap<PathBuf, Vec<DirEntry>> =
-            std::collections::HashMap::new();
+    /// 对路径列表构建的树应用过滤、排序和深度限制
+    fn prune_listing(&self, mut node: TreeNode) -> TreeNode {
+        if node.entry.depth() >= self.config.depth {
+            node.children.clear();
+            return node;
+        }
 
-        for entry in &entries {
-            if entry.depth() == 0 {
-                continue;
+        let children: Vec<TreeNode> = std::mem::take(&mut node.children)
+            .into_iter()
+            .filter(|child| self.filter_entry(&child.entry))
+            .map(|child| self.prune_listing(child))
+            .collect();
+        let children = self.sort_children(children, |node| &node.entry);
+
+        node.children = self.apply_filelimit(&mut node.entry, children);
+        node
+    }
+
+    /// 目录的条目数超过`--filelimit`时不进入该目录，只在条目上记录条目数
+    ///
+    /// 起始目录是用户明确要求列出的，总是展开。
+    fn apply_filelimit<T>(&self, dir: &mut Entry, children: Vec<T>) -> Vec<T> {
+        match self.config.args.filelimit {
+            Some(limit) if dir.depth() > 0 && children.len() > limit => {
+                dir.set_filelimit_exceeded(children.len());
+                Vec::new()
             }
+            _ => children,
+        }
+    }
 
-            if let Some(parent_path) = entry.path().parent() {
-                dir_groups
-                    .entry(parent_path.to_path_buf())
-                    .or_default()
-                    .push(entry.clone());
+    /// 在需要进入目录时读取其子条目，读取失败时在条目上记录错误
+    fn load_children(
+        &self,
+        entry: &mut Entry,
+        root_device: Option<u64>,
+        ancestors: &[FileId],
+    ) -> Vec<Entry> {
+        let descend = entry.file_type().is_dir()
+            && entry.depth() < self.config.depth
+            && !(self.config.args.samefilesystem
+                && entry.depth() > 0
+                && entry.device() != root_device);
+
+        if !descend {
+            return Vec::new();
+        }
+
+        // 跟随的链接指向当前路径上的某个目录时形成循环，不再进入
+        if entry.link_target().is_some()
+            && entry
+                .file_id()
+                .is_some_and(|file_id| ancestors.contains(&file_id))
+        {
+            entry.set_recursive_link();
+            return Vec::new();
+        }
+
+        match self.read_children(entry) {
+            Ok(children) => self.apply_filelimit(entry, children),
+            Err(err) => {
+                let message = err.to_string();
+                self.record_error(entry.path(), &message);
+                entry.set_error(message);
+                Vec::new()
             }
         }
+    }
 
-        // 递归构建结果（使用并行处理每个目录的排序）
-        let mut result = vec![root.clone()];
-        self.parallel_process_dir_groups(&dir_groups, &root, &mut result);
+    /// 统计路径及其下所有内容的大小，不跟随符号链接；无法读取的路径报告错误，不计入总大小
+    fn disk_usage(&self, path: &Path, mode: DuMode) -> u64 {
+        let meta = match std::fs::symlink_metadata(path) {
+            Ok(meta) => meta,
+            Err(err) => {
+                self.record_error(path, &err.to_string());
+                return 0;
+            }
+        };
+        let mut total = mode.size_of(&FileMeta::from_std(&meta));
 
-        result
+        if meta.is_dir() {
+            total += self
+                .read_dir_for_du(path)
+                .map(|child| self.disk_usage(&child.path(), mode))
+                .sum::<u64>();
+        }
+        total
+    }
+
+    /// 为`--du`读取目录，目录本身和其中的目录项读取失败时都报告错误
+    fn read_dir_for_du<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> impl Iterator<Item = std::fs::DirEntry> + 'a {
+        let read_dir = std::fs::read_dir(path)
+            .map_err(|err| self.record_error(path, &err.to_string()))
+            .ok();
+        read_dir.into_iter().flatten().filter_map(move |child| {
+            child
+                .map_err(|err| self.record_error(path, &err.to_string()))
+                .ok()
+        })
     }
 
-    /// 并行处理目录组 - 对每个目录的子条目使用并行排序
-    fn parallel_process_dir_groups(
+    /// 从目录项创建条目；读取失败的目录项报告错误后跳过
+    fn read_child(
         &self,
-        dir_groups: &std::collections::HashMap<PathBuf, Vec<DirEntry>>,
-        dir: &DirEntry,
-        result: &mut Vec<DirEntry>,
-    ) {
-        if let Some(children) = dir_groups.get(dir.path()) {
-            // 使用rayon并行排序当前目录的子条目
-            let mut sorted_children: Vec<DirEntry> =
-                children.par_iter().map(|e| e.clone()).collect();
-
-            sorted_children.par_sort_by(|a, b| {
-                // 目录排在文件前面
-                let a_is_dir = a.file_type().is_dir();
-                let b_is_dir = b.file_type().is_dir();
-
-                if a_is_dir != b_is_dir {
-                    return if a_is_dir {
-                        std::cmp::Ordering::Less
-                    } else {
-                        std::cmp::Ordering::Greater
-                    };
-                }
+        dir: &Entry,
+        child: io::Result<std::fs::DirEntry>,
+        depth: usize,
+        follow_links: bool,
+    ) -> Option<Entry> {
+        let child = match child {
+            Ok(child) => child,
+            Err(err) => {
+                self.record_error(dir.path(), &err.to_string());
+                return None;
+            }
+        };
+        match Entry::from_dir_entry(&child, depth, follow_links) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                self.record_error(&child.path(), &err.to_string());
+                None
+            }
+        }
+    }
 
-                // 对于同一类型的条目，按指定顺序排序
-                match self.config.args.sort.as_deref() {
-                    Some("time") => {
-                        let a_meta = a.metadata().unwrap();
-                        let b_meta = b.metadata().unwrap();
-                        b_meta.modified().unwrap().cmp(&a_meta.modified().unwrap())
-                    }
-                    Some(_) | None => a.file_name().cmp(b.file_name()),
-                }
+    /// 读取目录的直接子条目，完成过滤和排序
+    fn read_children(&self, dir: &Entry) -> std::io::Result<Vec<Entry>> {
+        let read_dir = std::fs::read_dir(dir.path())?;
+
+        let depth = dir.depth() + 1;
+        let follow_links = self.config.args.follow_links;
+        let children: Vec<Entry> = read_dir
+            .filter_map(|child| self.read_child(dir, child, depth, follow_links))
+            .filter(|child| !self.is_ignored(child.path(), child.file_type().is_dir()))
+            .filter(|child| self.filter_entry(child))
+            .inspect(|child| self.record_metadata_error(child))
+            .collect();
+
+        Ok(self.sort_children(children, |entry| entry))
+    }
+}
+
+/// 汇总路径列表构建的树中每个目录的大小
+fn aggregate_listing_sizes(node: &mut TreeNode, mode: DuMode) -> u64 {
+    let mut total = node.entry.metadata().map_or(0, |meta| mode.size_of(meta));
+    for child in &mut node.children {
+        total += aggregate_listing_sizes(child, mode);
+    }
+    node.entry.set_size(total);
+    total
+}
+
+/// 解析路径列表中的一条记录
+///
+/// 只有第二列是数字时才视为元数据列，否则整条记录都是路径（路径本身可能含有制表符）。
+/// 只有元数据列需要是UTF-8，路径按原始字节使用。
+fn parse_listing_record(record: &[u8]) -> (PathBuf, FileKind, Option<FileMeta>) {
+    let mut path = record;
+    let mut meta = None;
+
+    if let Some(tab) = record.iter().position(|&byte| byte == b'\t')
+        && let Ok(rest) = std::str::from_utf8(&record[tab + 1..])
+    {
+        let mut columns = rest.split('\t').map(str::trim);
+        if let Some(size) = columns.next().and_then(|column| column.parse().ok()) {
+            let modified = columns
+                .next()
+                .and_then(|column| column.parse().ok())
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+            path = &record[..tab];
+            meta = Some(FileMeta {
+                size,
+                modified,
+                ..FileMeta::default()
             });
+        }
+    }
 
-            // 添加排序后的子条目
-            for child in sorted_children {
-                result.push(child.clone());
+    // 以路径分隔符结尾的路径是目录，与`TreeNode::from_paths`一致
+    let kind = if path
+        .last()
+        .is_some_and(|&byte| std::path::is_separator(byte as char))
+    {
+        FileKind::Dir
+    } else {
+        FileKind::File
+    };
+    (path_from_bytes(path), kind, meta)
+}
 
-                if child.file_type().is_dir() {
-                    self.parallel_process_dir_groups(dir_groups, &child, result);
+/// 把路径列表中的原始字节转换为路径
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+/// 把路径列表中的原始字节转换为路径，非Unix平台的路径不是任意字节，按UTF-8解码
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// 名称的排序键：去掉重音等组合符号，并把没有分解形式的字母换成基本字母，
+/// 使`é`、`ł`、`ø`排在`e`、`l`、`o`旁边而不是`z`后面；`ignore_case`时再转为小写
+///
+/// 这只是按基本字母排序，不是完整的Unicode排序算法。
+fn collation_key(name: &OsStr, ignore_case: bool) -> String {
+    let name = name.to_string_lossy();
+
+    // 纯ASCII名称没有需要处理的字母，跳过规范分解
+    let base = if name.is_ascii() {
+        name.into_owned()
+    } else {
+        let mut base = String::with_capacity(name.len());
+        for c in name.nfd().filter(|&c| !is_combining_mark(c)) {
+            match base_letters(c) {
+                Some(letters) => base.push_str(letters),
+                None => base.push(c),
+            }
+        }
+        base
+    };
+
+    if ignore_case {
+        base.to_lowercase()
+    } else {
+        base
+    }
+}
+
+/// 没有Unicode分解形式的拉丁字母对应的基本字母
+fn base_letters(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ł' => "l",
+        'Ł' => "L",
+        'ø' => "o",
+        'Ø' => "O",
+        'đ' => "d",
+        'Đ' => "D",
+        'ħ' => "h",
+        'Ħ' => "H",
+        'ı' => "i",
+        'ß' => "ss",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'þ' => "th",
+        'Þ' => "TH",
+        _ => return None,
+    })
+}
+
+/// 按版本号比较两个名称：连续的数字按数值比较，例如`file2`排在`file10`前面
+///
+/// 数值相同（例如`01`和`1`）时返回`Equal`，由调用者按原始名称决定顺序。
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_rest, mut b_rest) = (a.as_bytes(), b.as_bytes());
+
+    loop {
+        match (a_rest.first(), b_rest.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (a_num, a_tail) = split_digits(a_rest);
+                let (b_num, b_tail) = split_digits(b_rest);
+
+                // 去掉前导零后，位数多的数值大；位数相同时按字典序比较
+                let a_trimmed = trim_leading_zeros(a_num);
+                let b_trimmed = trim_leading_zeros(b_num);
+                let ordering = a_trimmed
+                    .len()
+                    .cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
                 }
+                (a_rest, b_rest) = (a_tail, b_tail);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a_rest, b_rest) = (&a_rest[1..], &b_rest[1..]);
             }
         }
     }
 }
+
+/// 拆分开头的连续数字
+fn split_digits(bytes: &[u8]) -> (&[u8], &[u8]) {
+    let end = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_digit())
+        .unwrap_or(bytes.len());
+    bytes.split_at(end)
+}
+
+/// 去掉数字开头的零
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let start = digits
+        .iter()
+        .position(|&byte| byte != b'0')
+        .unwrap_or(digits.len());
+    &digits[start..]
+}
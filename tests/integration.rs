@@ -1,50 +1,89 @@
 //! 集成测试 - 测试treecmd的主要功能
 
 use std::path::PathBuf;
-use treecmd::{Args, Config, Formatter, Walker};
+use treecmd::{Args, Config, ConfigBuilder, Formatter, Pattern, SortKey, Walker};
 
 /// 使用当前目录进行测试
 fn get_test_path() -> PathBuf {
     std::env::current_dir().expect("Failed to get current dir")
 }
 
+/// 创建测试用的临时目录
+fn temp_dir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("treecmd-test")
+        .tempdir()
+        .expect("Failed to create temp dir")
+}
+
+/// 分别以串行和`threads(2)`并行方式遍历，返回两次按显示顺序访问的路径
+fn walked_paths(builder: ConfigBuilder) -> [Vec<PathBuf>; 2] {
+    walked_paths_with(builder, |_| {})
+}
+
+/// 同`walked_paths`，遍历之前先用`prepare`设置遍历器
+///
+/// 与程序的选择一致：串行时使用流式遍历，`--du`需要完整的目录树时使用`walk_tree`。
+fn walked_paths_with(builder: ConfigBuilder, prepare: impl Fn(&mut Walker)) -> [Vec<PathBuf>; 2] {
+    [builder.clone(), builder.threads(2)].map(|builder| {
+        let config = builder.build().unwrap();
+        let streaming = config.args.threads.is_none() && config.args.du.is_none();
+        let mut walker = Walker::new(config);
+        prepare(&mut walker);
+
+        let mut paths = Vec::new();
+        if streaming {
+            walker
+                .walk_streaming(|entry, _| {
+                    paths.push(entry.path().to_path_buf());
+                    Ok::<(), std::io::Error>(())
+                })
+                .unwrap();
+        } else {
+            let tree = walker.walk_tree().unwrap();
+            tree.visit(&mut |entry, _| paths.push(entry.path().to_path_buf()));
+        }
+        paths
+    })
+}
+
+/// 从路径列表构建以`x`为根的目录树，并渲染为不带颜色的文本
+fn render_listing(builder: ConfigBuilder, listing: &str) -> String {
+    let config = builder.path("x").no_color(true).build().unwrap();
+    let tree = Walker::new(config.clone())
+        .walk_listing(listing.as_bytes())
+        .unwrap();
+    Formatter::new(config).tree_to_string(&tree)
+}
+
+/// 从路径列表构建以`x`为根的目录树，返回根目录下各条目按显示顺序的名称
+fn listing_names(builder: ConfigBuilder, listing: &str) -> Vec<String> {
+    let config = builder.path("x").build().unwrap();
+    let tree = Walker::new(config)
+        .walk_listing(listing.as_bytes())
+        .unwrap();
+    tree.children
+        .iter()
+        .map(|child| child.entry.file_name().to_string_lossy().into_owned())
+        .collect()
+}
+
 #[test]
 fn test_basic_walk() {
     let test_path = get_test_path();
 
     let args = Args {
-        all: false,
-        ascii: false,
-        color: false,
-        dirs_only: false,
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
         level: Some(2), // 限制深度为2，避免遍历太多
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: None,
+        exclude_pattern: None,
+        include_pattern: None,
         depth: 2,
     };
 
@@ -66,37 +105,18 @@ fn test_parallel_walk() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
-        dirs_only: false,
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
         level: Some(2),
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
         threads: Some(2),
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: None,
+        exclude_pattern: None,
+        include_pattern: None,
         depth: 2,
     };
 
@@ -114,37 +134,17 @@ fn test_parallel_vs_serial_consistency() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
-        dirs_only: false,
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
         level: Some(2),
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: None,
+        exclude_pattern: None,
+        include_pattern: None,
         depth: 2,
     };
 
@@ -171,37 +171,17 @@ fn test_sort_entries() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
-        dirs_only: false,
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
         level: Some(2),
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: None,
+        exclude_pattern: None,
+        include_pattern: None,
         depth: 2,
     };
 
@@ -221,37 +201,22 @@ fn test_json_output_structure() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
-        dirs_only: false,
         mtime: true,
-        filelimit: true,
+        classify: true,
         full_path: true,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
         size: true,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
         level: Some(2),
         path: Some(test_path.clone()),
         json: true,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: None,
+        exclude_pattern: None,
+        include_pattern: None,
         depth: 2,
     };
 
@@ -270,37 +235,18 @@ fn test_dirs_only_filter() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
         dirs_only: true, // 仅目录
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
         level: Some(2),
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: None,
+        exclude_pattern: None,
+        include_pattern: None,
         depth: 2,
     };
 
@@ -321,37 +267,18 @@ fn test_exclude_pattern() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
-        dirs_only: false,
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: Some("target".to_string()), // 排除target目录
-        follow_links: false,
+        exclude: vec!["target".to_string()], // 排除target目录
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
         level: Some(3),
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: Some(regex::Regex::new("target").unwrap()),
-        include_regex: None,
+        exclude_pattern: Some(Pattern::glob(&["target"], false).unwrap()),
+        include_pattern: None,
         depth: 3,
     };
 
@@ -372,37 +299,18 @@ fn test_include_pattern() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
-        dirs_only: false,
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: Some("Cargo".to_string()), // 只包含Cargo相关文件
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: None,
-        uid: false,
-        samefilesystem: false,
+        include: vec!["Cargo".to_string()], // 只包含Cargo相关文件
         level: Some(2),
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: Some(regex::Regex::new("Cargo").unwrap()),
+        exclude_pattern: None,
+        include_pattern: Some(Pattern::regex(&["Cargo"], false).unwrap()),
         depth: 2,
     };
 
@@ -427,37 +335,18 @@ fn test_sort_by_time() {
 
     let args = Args {
         all: true,
-        ascii: false,
-        color: false,
-        dirs_only: false,
-        mtime: false,
-        filelimit: false,
-        full_path: false,
-        gid: false,
-        noreport: false,
-        exclude: None,
-        follow_links: false,
         no_color: true,
-        literal: false,
-        include: None,
-        perms: false,
-        quiet: false,
-        size: false,
-        sort: Some("time".to_string()), // 按时间排序
-        uid: false,
-        samefilesystem: false,
+        sort: Some(SortKey::Mtime), // 按时间排序
         level: Some(2),
         path: Some(test_path.clone()),
-        json: false,
-        progress: false,
-        threads: None,
+        ..Args::default()
     };
 
     let config = Config {
         args: args.clone(),
         color_enabled: false,
-        exclude_regex: None,
-        include_regex: None,
+        exclude_pattern: None,
+        include_pattern: None,
         depth: 2,
     };
 
@@ -468,3 +357,1392 @@ fn test_sort_by_time() {
     // 验证排序成功（不崩溃）
     assert!(!sorted.is_empty(), "排序应该返回结果");
 }
+
+#[test]
+fn test_format_mode() {
+    use treecmd::perms::format_mode;
+
+    assert_eq!(format_mode(0o040755), "drwxr-xr-x");
+    assert_eq!(format_mode(0o100644), "-rw-r--r--");
+    assert_eq!(format_mode(0o120777), "lrwxrwxrwx");
+    // setuid/setgid/sticky位
+    assert_eq!(format_mode(0o104755), "-rwsr-xr-x");
+    assert_eq!(format_mode(0o102644), "-rw-r-Sr--");
+    assert_eq!(format_mode(0o041777), "drwxrwxrwt");
+    assert_eq!(format_mode(0o041776), "drwxrwxrwT");
+    // 特殊文件类型
+    assert_eq!(format_mode(0o140755), "srwxr-xr-x");
+    assert_eq!(format_mode(0o010644), "prw-r--r--");
+    assert_eq!(format_mode(0o060660), "brw-rw----");
+    assert_eq!(format_mode(0o020620), "crw--w----");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_owner_cache() {
+    use treecmd::perms::OwnerCache;
+
+    let cache = OwnerCache::new();
+
+    // 无法解析的ID回退为数字
+    assert_eq!(cache.user_name(3_999_999_999), "3999999999");
+    assert_eq!(cache.group_name(3_999_999_999), "3999999999");
+
+    // UID 0在类Unix系统上总是root
+    assert_eq!(cache.user_name(0), "root");
+    assert_eq!(cache.user_name(0), "root", "缓存命中应返回相同结果");
+}
+
+#[test]
+fn test_gitignore_filter() {
+    use std::fs;
+
+    let dir = temp_dir();
+    let root = dir.path();
+
+    fs::create_dir_all(root.join(".git/info")).unwrap();
+    fs::write(root.join(".git/info/exclude"), "excluded.txt\n").unwrap();
+    fs::write(
+        root.join(".gitignore"),
+        "target/\n*.log\n!keep.log\nbuild/\n",
+    )
+    .unwrap();
+    // .ignore 的优先级高于 .gitignore
+    fs::write(root.join(".ignore"), "!build/\n").unwrap();
+    fs::create_dir_all(root.join("target/debug")).unwrap();
+    fs::write(root.join("target/debug/app"), "").unwrap();
+    fs::create_dir_all(root.join("build")).unwrap();
+    fs::write(root.join("build/out.o"), "").unwrap();
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("sub/.gitignore"), "secret.txt\n").unwrap();
+    fs::write(root.join("sub/secret.txt"), "").unwrap();
+    fs::write(root.join("sub/public.txt"), "").unwrap();
+    fs::write(root.join("app.log"), "").unwrap();
+    fs::write(root.join("keep.log"), "").unwrap();
+    fs::write(root.join("excluded.txt"), "").unwrap();
+
+    let args = Args {
+        no_color: true,
+        level: Some(5),
+        path: Some(root.to_path_buf()),
+        gitignore: true,
+        ..Args::default()
+    };
+
+    let config = Config {
+        args: args.clone(),
+        color_enabled: false,
+        exclude_pattern: None,
+        include_pattern: None,
+        depth: 5,
+    };
+
+    let walker = Walker::new(config);
+    let mut names: Vec<String> = walker
+        .walk()
+        .filter(|e| e.depth() > 0)
+        .map(|e| {
+            e.path()
+                .strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect();
+    names.sort();
+
+    assert_eq!(
+        names,
+        vec!["build", "build/out.o", "keep.log", "sub", "sub/public.txt"]
+    );
+
+    // 程序实际使用的串行流式遍历和并行遍历得到相同的结果
+    let builder = Config::builder().path(root).level(5).gitignore(true);
+    let expected: Vec<PathBuf> = [
+        "",
+        "build",
+        "build/out.o",
+        "keep.log",
+        "sub",
+        "sub/public.txt",
+    ]
+    .iter()
+    .map(|path| root.join(path))
+    .collect();
+    for paths in walked_paths(builder) {
+        assert_eq!(paths, expected);
+    }
+}
+
+#[test]
+fn test_parallel_matches_serial_order() {
+    use std::fs;
+
+    let dir = temp_dir();
+    let root = dir.path();
+
+    for d in ["b", "a", "a/z", "a/y", "c/d/e"] {
+        fs::create_dir_all(root.join(d)).unwrap();
+    }
+    for f in ["x.txt", "a/2.txt", "a/1.txt", "a/z/f", "c/d/e/g", "c/h"] {
+        fs::write(root.join(f), "").unwrap();
+    }
+
+    let args = Args {
+        all: true,
+        no_color: true,
+        level: Some(3),
+        path: Some(root.to_path_buf()),
+        threads: Some(4),
+        ..Args::default()
+    };
+
+    let config = Config {
+        args: args.clone(),
+        color_enabled: false,
+        exclude_pattern: None,
+        include_pattern: None,
+        depth: 3,
+    };
+
+    let walker = Walker::new(config);
+    let serial: Vec<PathBuf> = walker
+        .sort_entries(walker.walk().collect())
+        .iter()
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    let parallel: Vec<PathBuf> = walker
+        .walk_parallel()
+        .iter()
+        .map(|e| e.path().to_path_buf())
+        .collect();
+
+    assert_eq!(serial, parallel, "并行遍历应与串行排序结果顺序一致");
+    // 深度限制为3，c/d/e/g 不应出现
+    assert!(!parallel.contains(&root.join("c/d/e/g")));
+    assert_eq!(parallel[1], root.join("a"), "目录应按名称排在前面");
+}
+
+#[test]
+fn test_unreadable_paths_are_reported() {
+    let missing = std::env::temp_dir().join("treecmd-test-does-not-exist");
+
+    let args = Args {
+        no_color: true,
+        level: Some(2),
+        path: Some(missing.clone()),
+        ..Args::default()
+    };
+
+    let config = Config {
+        args: args.clone(),
+        color_enabled: false,
+        exclude_pattern: None,
+        include_pattern: None,
+        depth: 2,
+    };
+
+    // 串行遍历
+    let walker = Walker::new(config.clone());
+    let entries: Vec<_> = walker.walk().collect();
+    assert!(entries.is_empty());
+    let errors = walker.errors();
+    assert_eq!(errors.len(), 1, "不存在的起始路径应被报告");
+    assert_eq!(errors[0].path, missing);
+
+    // 并行遍历
+    let walker = Walker::new(config);
+    assert!(walker.walk_parallel().is_empty());
+    assert_eq!(walker.errors().len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_permission_denied_dir_is_annotated() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    // root用户可以读取任何目录，无法构造权限错误，跳过而不是空跑通过
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("skipping test_permission_denied_dir_is_annotated: running as root");
+        return;
+    }
+
+    let dir = temp_dir();
+    let root = dir.path();
+    let locked = root.join("locked");
+    fs::create_dir(&locked).unwrap();
+    fs::write(locked.join("secret"), "").unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+    let args = Args {
+        no_color: true,
+        level: Some(2),
+        path: Some(root.to_path_buf()),
+        threads: Some(2),
+        ..Args::default()
+    };
+
+    let config = Config {
+        args: args.clone(),
+        color_enabled: false,
+        exclude_pattern: None,
+        include_pattern: None,
+        depth: 2,
+    };
+
+    let walker = Walker::new(config.clone());
+    let serial = walker.sort_entries(walker.walk().collect());
+    let parallel = walker.walk_parallel();
+
+    // 程序默认使用的串行流式遍历
+    let streaming_walker = Walker::new(Config {
+        args: Args {
+            threads: None,
+            ..config.args.clone()
+        },
+        ..config
+    });
+    let mut streamed = Vec::new();
+    streaming_walker
+        .walk_streaming(|entry, _| {
+            streamed.push(entry);
+            Ok::<(), std::io::Error>(())
+        })
+        .unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+    for entries in [&serial, &parallel, &streamed] {
+        let entry = entries.iter().find(|e| e.path() == locked).unwrap();
+        assert!(entry.error().is_some(), "无法打开的目录应带有错误信息");
+    }
+    for walker in [&walker, &streaming_walker] {
+        assert_eq!(walker.errors().len(), 1);
+        assert_eq!(walker.errors()[0].path, locked);
+    }
+}
+
+#[test]
+fn test_streaming_walk_order() {
+    use std::fs;
+
+    let dir = temp_dir();
+    let root = dir.path();
+
+    for d in ["b", "a", "a/z", "a/y"] {
+        fs::create_dir_all(root.join(d)).unwrap();
+    }
+    for f in ["x.txt", "a/2.txt", "a/1.txt", "a/z/f"] {
+        fs::write(root.join(f), "").unwrap();
+    }
+
+    let args = Args {
+        all: true,
+        no_color: true,
+        dirsfirst: true, // 目录在前，便于检查每个目录的最后一个条目
+        level: Some(3),
+        path: Some(root.to_path_buf()),
+        ..Args::default()
+    };
+
+    let config = Config {
+        args: args.clone(),
+        color_enabled: false,
+        exclude_pattern: None,
+        include_pattern: None,
+        depth: 3,
+    };
+
+    let walker = Walker::new(config);
+    let sorted: Vec<PathBuf> = walker
+        .sort_entries(walker.walk().collect())
+        .iter()
+        .map(|e| e.path().to_path_buf())
+        .collect();
+
+    let mut streamed = Vec::new();
+    walker
+        .walk_streaming(|entry, is_last| {
+            streamed.push((entry.path().to_path_buf(), is_last));
+            Ok::<(), std::io::Error>(())
+        })
+        .unwrap();
+
+    let streamed_paths: Vec<PathBuf> = streamed.iter().map(|(p, _)| p.clone()).collect();
+    assert_eq!(streamed_paths, sorted, "流式遍历应与排序结果顺序一致");
+
+    // 每个目录的最后一个子条目被标记为is_last
+    let last: Vec<PathBuf> = streamed
+        .iter()
+        .filter(|(_, is_last)| *is_last)
+        .map(|(p, _)| p.strip_prefix(root).unwrap().to_path_buf())
+        .collect();
+    assert_eq!(
+        last,
+        vec![
+            PathBuf::new(),
+            PathBuf::from("a/z/f"),
+            PathBuf::from("a/2.txt"),
+            PathBuf::from("x.txt"),
+        ]
+    );
+}
+
+#[test]
+fn test_tree_from_entries() {
+    use treecmd::{Entry, FileKind, TreeNode};
+
+    let entry = |path: &str, depth: usize, is_dir: bool| {
+        let kind = if is_dir {
+            FileKind::Dir
+        } else {
+            FileKind::File
+        };
+        Entry::new(path, depth, kind)
+    };
+
+    let entries = vec![
+        entry("r", 0, true),
+        entry("r/a", 1, true),
+        entry("r/a/1", 2, false),
+        entry("r/a/2", 2, false),
+        // 父目录不在列表中的孤立条目应被丢弃
+        entry("r/missing/x", 2, false),
+        entry("r/b", 1, false),
+    ];
+
+    let tree = TreeNode::from_entries(entries).expect("应该构建出目录树");
+    assert_eq!(tree.children.len(), 2);
+    assert_eq!(tree.children[0].children.len(), 2);
+
+    let mut visited = Vec::new();
+    tree.visit(&mut |entry, is_last| {
+        visited.push((entry.path().display().to_string(), is_last));
+    });
+    let expected: Vec<(String, bool)> = [
+        ("r", true),
+        ("r/a", false),
+        ("r/a/1", false),
+        ("r/a/2", true),
+        ("r/b", true),
+    ]
+    .iter()
+    .map(|(p, l)| (p.to_string(), *l))
+    .collect();
+    assert_eq!(visited, expected);
+
+    // 没有根条目时无法构建
+    assert!(TreeNode::from_entries(vec![entry("r/a", 1, true)]).is_none());
+}
+
+#[test]
+fn test_tree_from_paths() {
+    use treecmd::{FileKind, FileMeta, TreeNode};
+
+    let tree = TreeNode::from_paths(
+        "archive",
+        ["src/main.rs", "README.md", "src/lib.rs", "docs/", "src"],
+    );
+
+    assert_eq!(tree.entry.path(), std::path::Path::new("archive"));
+    assert!(tree.entry.file_type().is_dir());
+
+    let names: Vec<_> = tree
+        .children
+        .iter()
+        .map(|c| {
+            (
+                c.entry.file_name().to_string_lossy().to_string(),
+                c.entry.file_type(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("src".to_string(), FileKind::Dir),
+            ("README.md".to_string(), FileKind::File),
+            ("docs".to_string(), FileKind::Dir),
+        ]
+    );
+
+    let main_rs = &tree.children[0].children[0];
+    assert_eq!(
+        main_rs.entry.path(),
+        std::path::Path::new("archive/src/main.rs")
+    );
+    assert_eq!(main_rs.entry.depth(), 2);
+
+    // 带元数据的路径列表
+    let meta = FileMeta {
+        size: 42,
+        ..FileMeta::default()
+    };
+    let tree =
+        TreeNode::from_path_entries(".", vec![("a/b.bin", FileKind::File, Some(meta.clone()))]);
+    let b = &tree.children[0].children[0];
+    assert_eq!(b.entry.metadata(), Some(&meta));
+    assert!(tree.children[0].entry.file_type().is_dir());
+}
+
+#[test]
+fn test_config_builder() {
+    use treecmd::ConfigError;
+
+    let config = Config::builder()
+        .all(true)
+        .dirs_only(true)
+        .no_color(true)
+        .level(5)
+        .exclude("target")
+        .path("src")
+        .build()
+        .unwrap();
+    assert!(config.args.all);
+    assert!(config.args.dirs_only);
+    assert!(!config.color_enabled);
+    assert_eq!(config.depth, 5);
+    assert!(config.exclude_pattern.unwrap().is_match("target"));
+    assert_eq!(config.args.path, Some(PathBuf::from("src")));
+
+    // 默认值与不带参数的命令行一致
+    let config = Config::builder().build().unwrap();
+    assert_eq!(config.depth, 3);
+    assert!(config.exclude_pattern.is_none());
+
+    let err = Config::builder().include("[").build().unwrap_err();
+    assert!(matches!(
+        err,
+        ConfigError::InvalidPattern {
+            option: "--include",
+            ..
+        }
+    ));
+
+    let err = Config::builder()
+        .color(true)
+        .no_color(true)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, ConfigError::Conflict(..)));
+
+    let err = Config::builder().level(0).build().unwrap_err();
+    assert!(matches!(
+        err,
+        ConfigError::InvalidValue {
+            option: "--level",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_config_try_parse_from() {
+    use treecmd::ConfigError;
+
+    let config = Config::try_parse_from(["tree", "-d", "-L", "2", "-n", "src"]).unwrap();
+    assert!(config.args.dirs_only);
+    assert_eq!(config.depth, 2);
+    assert!(!config.color_enabled);
+    assert_eq!(config.args.path, Some(PathBuf::from("src")));
+
+    let err = Config::try_parse_from(["tree", "--no-such-option"]).unwrap_err();
+    assert!(matches!(err, ConfigError::Args(_)));
+
+    let err = Config::try_parse_from(["tree", "-I", "[a-"]).unwrap_err();
+    assert!(err.to_string().contains("--exclude"));
+}
+
+#[test]
+fn test_format_tree_to_writer() {
+    use treecmd::TreeNode;
+
+    let config = Config::builder().no_color(true).build().unwrap();
+    let mut formatter = Formatter::new(config);
+    let tree = TreeNode::from_paths("root", ["src/main.rs", "src/lib.rs", "README.md"]);
+
+    let text = formatter.tree_to_string(&tree);
+    assert!(text.starts_with("├── src\n│   ├── main.rs\n│   └── lib.rs\n└── README.md\n\n"));
+    assert!(text.trim_end().ends_with("3 files"));
+
+    // 写入任意io::Write与返回字符串的结果一致
+    let mut buf = Vec::new();
+    formatter.format_tree(&mut buf, &tree).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), text);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&formatter.json_to_string(Some(&tree), &[])).unwrap();
+    assert_eq!(json["name"], "root");
+    assert_eq!(json["children"][0]["children"][1]["name"], "lib.rs");
+}
+
+#[test]
+fn test_format_tree_broken_pipe() {
+    use std::io::{self, Write};
+    use treecmd::TreeNode;
+
+    /// 模拟下游已经关闭的管道
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let config = Config::builder().no_color(true).build().unwrap();
+    let mut formatter = Formatter::new(config);
+    let tree = TreeNode::from_paths("root", ["a", "b", "c"]);
+
+    let err = formatter.format_tree(&mut ClosedPipe, &tree).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    let err = formatter
+        .format_json(&mut ClosedPipe, Some(&tree), &[])
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn test_atomic_output_file() {
+    use std::io::Write;
+    use treecmd::AtomicFile;
+
+    let dir = temp_dir();
+    let target = dir.path().join("tree.txt");
+    std::fs::write(&target, "old").unwrap();
+
+    // 提交前目标文件保持原样
+    let mut file = AtomicFile::create(&target).unwrap();
+    file.write_all(b"new").unwrap();
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "old");
+    file.commit().unwrap();
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+
+    // 未提交的写入被丢弃，不留下临时文件
+    let mut file = AtomicFile::create(&target).unwrap();
+    file.write_all(b"partial").unwrap();
+    drop(file);
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    // 输出到文件时默认关闭彩色，-C仍然可以强制开启
+    let config = Config::builder().output(&target).build().unwrap();
+    assert!(!config.color_enabled);
+    let config = Config::builder()
+        .output(&target)
+        .color(true)
+        .build()
+        .unwrap();
+    assert!(config.color_enabled);
+}
+
+#[test]
+fn test_xml_output() {
+    use treecmd::{FileKind, FileMeta, TreeNode};
+
+    let meta = FileMeta {
+        size: 12,
+        ..FileMeta::default()
+    };
+    let tree = TreeNode::from_path_entries(
+        "root",
+        vec![
+            ("src/a&b.rs", FileKind::File, Some(meta.clone())),
+            ("empty/", FileKind::Dir, None),
+            ("latest", FileKind::Symlink, Some(meta)),
+        ],
+    );
+
+    let config = Config::builder().xml(true).size(true).build().unwrap();
+    let xml = Formatter::new(config).tree_to_string(&tree);
+    assert_eq!(
+        xml,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<tree>
+  <directory name="root">
+    <directory name="src">
+      <file name="a&amp;b.rs" size="12"></file>
+    </directory>
+    <directory name="empty">
+    </directory>
+    <link name="latest" size="12"></link>
+  </directory>
+  <report>
+    <directories>2</directories>
+    <files>2</files>
+  </report>
+</tree>
+"#
+    );
+
+    let err = Config::builder().xml(true).json(true).build().unwrap_err();
+    assert!(matches!(err, treecmd::ConfigError::Conflict(..)));
+}
+
+#[test]
+fn test_html_output() {
+    use treecmd::TreeNode;
+
+    let tree = TreeNode::from_paths("root", ["docs/read me.md", "bin/"]);
+
+    let config = Config::builder()
+        .html("https://example.com/files/")
+        .title("Artifacts <nightly>")
+        .build()
+        .unwrap();
+    let html = Formatter::new(config).tree_to_string(&tree);
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<title>Artifacts &lt;nightly&gt;</title>"));
+    // 不引用外部资源
+    assert!(!html.contains("<link") && !html.contains("<script"));
+    assert!(html.contains(
+        r#"<details open><summary><a href="https://example.com/files/docs/">docs</a></summary>"#
+    ));
+    assert!(html.contains(
+        r#"<div class="file"><a href="https://example.com/files/docs/read%20me.md">read me.md</a></div>"#
+    ));
+    assert_eq!(
+        html.matches("<details").count(),
+        html.matches("</details>").count()
+    );
+    assert!(html.trim_end().ends_with("</html>"));
+
+    let err = Config::builder().html(".").xml(true).build().unwrap_err();
+    assert!(matches!(
+        err,
+        treecmd::ConfigError::Conflict("--xml", "--html")
+    ));
+}
+
+#[test]
+fn test_gnu_json_output() {
+    use treecmd::{JsonFormat, TreeNode};
+
+    let tree = TreeNode::from_paths("root", ["src/main.rs", "README.md"]);
+
+    let config = Config::try_parse_from(["tree", "-J"]).unwrap();
+    assert!(config.args.json);
+    assert_eq!(config.args.json_format, Some(JsonFormat::Gnu));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&Formatter::new(config).json_to_string(Some(&tree), &[])).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {"type": "directory", "name": "root", "contents": [
+                {"type": "directory", "name": "src", "contents": [
+                    {"type": "file", "name": "main.rs"}
+                ]},
+                {"type": "file", "name": "README.md"}
+            ]},
+            {"type": "report", "directories": 1, "files": 2}
+        ])
+    );
+
+    // 原有结构仍然是默认值，--json-format单独使用时也启用JSON输出
+    let config = Config::builder()
+        .json_format(JsonFormat::Native)
+        .build()
+        .unwrap();
+    assert!(config.args.json);
+    let json: serde_json::Value =
+        serde_json::from_str(&Formatter::new(config).json_to_string(Some(&tree), &[])).unwrap();
+    assert_eq!(json["children"][0]["name"], "src");
+
+    // -J只能与--json-format=gnu一起使用
+    for format in ["native", "ndjson"] {
+        let err = Config::try_parse_from(["tree", "-J", "--json-format", format]).unwrap_err();
+        assert!(matches!(err, treecmd::ConfigError::Conflict(..)));
+    }
+    assert!(Config::try_parse_from(["tree", "-J", "--json-format", "gnu"]).is_ok());
+}
+
+#[test]
+fn test_ndjson_streaming_output() {
+    use treecmd::JsonFormat;
+
+    let dir = temp_dir();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/a.txt"), "abc").unwrap();
+
+    let config = Config::builder()
+        .json_format(JsonFormat::Ndjson)
+        .path(dir.path())
+        .build()
+        .unwrap();
+    let walker = Walker::new(config.clone());
+    let mut formatter = Formatter::new(config);
+
+    // 逐条回调时每个条目立即写出一行
+    let mut out = Vec::new();
+    let mut lines_seen = Vec::new();
+    formatter.begin_tree(&mut out).unwrap();
+    walker
+        .walk_streaming(|entry, is_last| {
+            formatter.write_tree_entry(&mut out, &entry, is_last)?;
+            lines_seen.push(out.iter().filter(|&&b| b == b'\n').count());
+            Ok::<(), std::io::Error>(())
+        })
+        .unwrap();
+    formatter.end_tree(&mut out).unwrap();
+    assert_eq!(lines_seen, vec![1, 2, 3]);
+
+    let lines: Vec<serde_json::Value> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines[0]["depth"], 0);
+    assert!(lines[0]["parent"].is_null());
+    assert_eq!(lines[1]["type"], "directory");
+    assert_eq!(lines[2]["type"], "file");
+    assert_eq!(lines[2]["size"], 3);
+    assert_eq!(lines[2]["parent"], lines[1]["path"]);
+}
+
+#[test]
+fn test_walk_listing() {
+    let config = Config::builder()
+        .path("repo")
+        .exclude("target")
+        .level(2)
+        .build()
+        .unwrap();
+    let walker = Walker::new(config);
+
+    // 换行分隔，带有大小和修改时间列；以/结尾的路径是目录
+    let listing = "src/main.rs\t120\t1700000000\nsrc/lib.rs\nempty/\ntarget/debug/app\nsrc/bin/tool.rs\n.hidden\n";
+    let tree = walker.walk_listing(listing.as_bytes()).unwrap();
+
+    let mut shown = Vec::new();
+    tree.visit(&mut |entry, _| shown.push(entry.path().to_path_buf()));
+    let expected: Vec<PathBuf> = [
+        "repo",
+        "repo/empty",
+        "repo/src",
+        "repo/src/bin",
+        "repo/src/lib.rs",
+        "repo/src/main.rs",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+    assert_eq!(shown, expected);
+
+    let main_rs = &tree.children[1].children[2].entry;
+    let meta = main_rs.metadata().unwrap();
+    assert_eq!(meta.size, 120);
+    assert_eq!(
+        meta.modified,
+        Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000))
+    );
+
+    // NUL分隔的记录，路径中可以含有换行符
+    let config = Config::builder().build().unwrap();
+    let tree = Walker::new(config).walk_listing(&b"b\nc\0a\0"[..]).unwrap();
+    let names: Vec<_> = tree
+        .children
+        .iter()
+        .map(|child| child.entry.file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, vec!["a", "b\nc"]);
+
+    // 不是UTF-8的文件名原样保留，元数据列照常解析
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let config = Config::builder().build().unwrap();
+        let tree = Walker::new(config)
+            .walk_listing(&b"caf\xe9\t42\n"[..])
+            .unwrap();
+        let entry = &tree.children[0].entry;
+        assert_eq!(entry.file_name().as_bytes(), b"caf\xe9");
+        assert_eq!(entry.metadata().unwrap().size, 42);
+    }
+}
+
+#[test]
+fn test_filelimit_and_classify() {
+    let dir = temp_dir();
+    std::fs::create_dir(dir.path().join("big")).unwrap();
+    for i in 0..5 {
+        std::fs::write(dir.path().join(format!("big/f{i}")), "").unwrap();
+    }
+    std::fs::create_dir(dir.path().join("small")).unwrap();
+    std::fs::write(dir.path().join("small/a"), "").unwrap();
+    #[cfg(unix)]
+    {
+        let fifo = std::ffi::CString::new(dir.path().join("pipe").to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o644) }, 0);
+    }
+
+    let config = Config::builder()
+        .path(dir.path())
+        .filelimit(3)
+        .classify(true)
+        .dirsfirst(true)
+        .no_color(true)
+        .build()
+        .unwrap();
+
+    // 串行和并行遍历都不进入条目过多的目录
+    for tree in [
+        Walker::new(config.clone()).walk_tree().unwrap(),
+        Walker::new(Config {
+            args: Args {
+                threads: Some(2),
+                ..config.args.clone()
+            },
+            ..config.clone()
+        })
+        .walk_tree()
+        .unwrap(),
+    ] {
+        let big = &tree.children[0];
+        assert_eq!(big.entry.filelimit_exceeded(), Some(5));
+        assert!(big.children.is_empty());
+        assert_eq!(tree.children[1].children.len(), 1);
+
+        let text = Formatter::new(config.clone()).tree_to_string(&tree);
+        assert!(text.contains("big/ [5 entries exceeds filelimit, not opening dir]\n"));
+        assert!(text.contains("small/\n"));
+        #[cfg(unix)]
+        assert!(text.contains("pipe|\n"));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_targets_and_cycles() {
+    use std::os::unix::fs::symlink;
+
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+    symlink("..", dir.path().join("a/b/up")).unwrap();
+    symlink("nowhere", dir.path().join("dangling")).unwrap();
+
+    let config = Config::builder()
+        .path(dir.path())
+        .follow_links(true)
+        .level(10)
+        .no_color(true)
+        .build()
+        .unwrap();
+
+    for tree in [
+        Walker::new(config.clone()).walk_tree().unwrap(),
+        Walker::new(Config {
+            args: Args {
+                threads: Some(2),
+                ..config.args.clone()
+            },
+            ..config.clone()
+        })
+        .walk_tree()
+        .unwrap(),
+    ] {
+        // 指向上级目录的链接不会被无限跟随
+        let up = &tree.children[0].children[0].children[0];
+        assert_eq!(up.entry.link_target(), Some(std::path::Path::new("..")));
+        assert!(up.entry.is_recursive_link());
+        assert!(up.children.is_empty());
+
+        let dangling = &tree.children[1].entry;
+        assert!(dangling.is_broken_link());
+        assert!(dangling.file_type().is_symlink());
+
+        let text = Formatter::new(config.clone()).tree_to_string(&tree);
+        assert!(text.contains("up -> .. [recursive, not followed]\n"));
+        assert!(text.contains("dangling -> nowhere\n"));
+
+        // -F不会因为链接的0o777模式位而添加*
+        let classify = Config {
+            args: Args {
+                classify: true,
+                ..config.args.clone()
+            },
+            ..config.clone()
+        };
+        let text = Formatter::new(classify).tree_to_string(&tree);
+        assert!(text.contains("a/\n"));
+        assert!(text.contains("up/ -> .. [recursive, not followed]\n"));
+        assert!(text.contains("dangling -> nowhere\n"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&Formatter::new(config.clone()).json_to_string(Some(&tree), &[]))
+                .unwrap();
+        assert_eq!(json["children"][1]["target"], "nowhere");
+    }
+}
+
+#[test]
+fn test_du_aggregates_hidden_and_deep_entries() {
+    use treecmd::DuMode;
+
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+    std::fs::write(dir.path().join("a/b/c/deep.bin"), vec![0u8; 1000]).unwrap();
+    std::fs::write(dir.path().join("a/.hidden"), vec![0u8; 300]).unwrap();
+    std::fs::write(dir.path().join("top.txt"), vec![0u8; 20]).unwrap();
+
+    // 期望值：不做任何过滤的全部条目大小之和（包括目录本身）
+    let apparent = |path: &std::path::Path| -> u64 {
+        walkdir::WalkDir::new(path)
+            .into_iter()
+            .map(|e| e.unwrap().metadata().unwrap().len())
+            .sum()
+    };
+
+    let config = Config::builder()
+        .path(dir.path())
+        .du(DuMode::Apparent)
+        .level(1)
+        .no_color(true)
+        .build()
+        .unwrap();
+    assert!(config.args.size, "--du应当显示大小列");
+
+    let tree = Walker::new(config.clone()).walk_tree().unwrap();
+    // 只显示第一层，但总大小包含隐藏文件和更深的文件
+    assert!(tree.children[0].children.is_empty());
+    assert_eq!(
+        tree.children[0].entry.metadata().unwrap().size,
+        apparent(&dir.path().join("a"))
+    );
+    assert_eq!(tree.entry.metadata().unwrap().size, apparent(dir.path()));
+    assert_eq!(tree.children[1].entry.metadata().unwrap().size, 20);
+
+    let text = Formatter::new(config).tree_to_string(&tree);
+    assert!(text.contains(" used in 1 directory"));
+}
+
+#[test]
+fn test_du_sorts_by_aggregated_size() {
+    use treecmd::{DuMode, SortKey};
+
+    let dir = temp_dir();
+    std::fs::create_dir(dir.path().join("big")).unwrap();
+    std::fs::write(dir.path().join("big/data.bin"), vec![0u8; 100_000]).unwrap();
+    std::fs::write(dir.path().join("mid"), vec![0u8; 5000]).unwrap();
+    std::fs::write(dir.path().join("small"), vec![0u8; 10]).unwrap();
+
+    // 目录自身的大小小于mid，但汇总后最大
+    let builder = Config::builder()
+        .path(dir.path())
+        .du(DuMode::Apparent)
+        .sort(SortKey::Size);
+    for paths in walked_paths(builder) {
+        let expected: Vec<PathBuf> = ["", "big", "big/data.bin", "mid", "small"]
+            .iter()
+            .map(|path| dir.path().join(path))
+            .collect();
+        assert_eq!(paths, expected);
+    }
+}
+
+#[test]
+fn test_size_units() {
+    // 用路径列表中的大小列构造TB和PB级别的文件
+    let listing = "a\t3\nb\t2048\nc\t1500000\nd\t5000000000000\ne\t3000000000000000\n";
+
+    // -s显示右对齐的字节数
+    let raw = render_listing(Config::builder().size(true), listing);
+    assert!(raw.contains("├──           3  a\n"));
+    assert!(raw.contains("└── 3000000000000000  e\n"));
+
+    // -h使用1024进制，隐含-s
+    let human = render_listing(Config::builder().human(true), listing);
+    assert!(human.contains("├──    3  a\n"));
+    assert!(human.contains("├── 2.0K  b\n"));
+    assert!(human.contains("├── 1.4M  c\n"));
+    assert!(human.contains("├── 4.5T  d\n"));
+    assert!(human.contains("└── 2.7P  e\n"));
+
+    // --si使用1000进制
+    let si = render_listing(Config::builder().si(true), listing);
+    assert!(si.contains("├── 2.0k  b\n"));
+    assert!(si.contains("├── 1.5M  c\n"));
+    assert!(si.contains("├── 5.0T  d\n"));
+    assert!(si.contains("└── 3.0P  e\n"));
+}
+
+#[test]
+fn test_size_shows_directory_own_size() {
+    let dir = temp_dir();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/file.txt"), vec![0u8; 5000]).unwrap();
+
+    let config = Config::builder()
+        .path(dir.path())
+        .size(true)
+        .no_color(true)
+        .build()
+        .unwrap();
+    let tree = Walker::new(config.clone()).walk_tree().unwrap();
+    let text = Formatter::new(config).tree_to_string(&tree);
+
+    // 与GNU tree相同：没有--du时目录显示其自身的大小，而不是内容之和
+    let own = std::fs::metadata(dir.path().join("sub")).unwrap().len();
+    assert!(
+        text.contains(&format!("└── {:>11}  sub\n", own)),
+        "{}",
+        text
+    );
+    assert!(text.contains(&format!("    └── {:>11}  file.txt\n", 5000)));
+}
+
+#[test]
+fn test_time_formats() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let listing = format!(
+        "old\t1\t1577934245\nrecent\t1\t{}\n",
+        now - 3 * 24 * 60 * 60
+    );
+
+    // strftime格式，隐含-D
+    let text = render_listing(
+        Config::builder().timefmt("%Y-%m-%d %H:%M:%S").utc(true),
+        &listing,
+    );
+    assert!(text.contains("├── 2020-01-02 03:04:05  old\n"));
+
+    // 默认格式：半年前的时间显示年份
+    let text = render_listing(Config::builder().mtime(true).utc(true), &listing);
+    assert!(text.contains("├── Jan  2  2020  old\n"));
+
+    // 相对时间
+    let text = render_listing(Config::builder().timefmt("relative"), &listing);
+    assert!(text.contains("└── 3 days ago  recent\n"));
+
+    // 无效的格式在创建配置时报错
+    let err = Config::builder().timefmt("%Q").build().unwrap_err();
+    assert!(err.to_string().contains("--timefmt"));
+}
+
+#[test]
+fn test_sort_keys() {
+    let listing = "f10.txt\t5\t300\nf2.rs\t50\t100\nf1\t500\t200\nREADME\t50\t400\n";
+
+    assert_eq!(
+        listing_names(Config::builder(), listing),
+        ["README", "f1", "f10.txt", "f2.rs"]
+    );
+    assert_eq!(
+        listing_names(Config::builder().sort(SortKey::Version), listing),
+        ["README", "f1", "f2.rs", "f10.txt"]
+    );
+    // 大小相同时按名称
+    assert_eq!(
+        listing_names(Config::builder().sort(SortKey::Size), listing),
+        ["f1", "README", "f2.rs", "f10.txt"]
+    );
+    assert_eq!(
+        listing_names(
+            Config::builder().sort(SortKey::Mtime).reverse(true),
+            listing
+        ),
+        ["f2.rs", "f1", "f10.txt", "README"]
+    );
+    assert_eq!(
+        listing_names(Config::builder().sort(SortKey::Extension), listing),
+        ["README", "f1", "f2.rs", "f10.txt"]
+    );
+    // 不排序时保持输入的顺序
+    assert_eq!(
+        listing_names(Config::builder().unsorted(true), listing),
+        ["f10.txt", "f2.rs", "f1", "README"]
+    );
+
+    // 串行和并行遍历的顺序一致
+    let dir = temp_dir();
+    for (name, len) in [("a", 30), ("b", 10), ("c", 20), ("d", 10)] {
+        std::fs::write(dir.path().join(name), vec![0u8; len]).unwrap();
+    }
+    let [serial, parallel] = walked_paths(
+        Config::builder()
+            .path(dir.path())
+            .sort(SortKey::Size)
+            .reverse(true),
+    );
+    assert_eq!(serial, parallel);
+    assert_eq!(
+        serial[1..],
+        [
+            dir.path().join("d"),
+            dir.path().join("b"),
+            dir.path().join("c"),
+            dir.path().join("a")
+        ]
+    );
+
+    // 未知的排序方式和冲突的选项都会报错
+    assert!(Config::try_parse_from(["tree", "--sort", "bogus"]).is_err());
+    assert!(
+        Config::builder()
+            .unsorted(true)
+            .sort(SortKey::Size)
+            .build()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_dirsfirst_and_filesfirst() {
+    let listing = "b/x\na\nc/\nd\n";
+
+    // 默认与GNU tree相同，目录和文件混合排序
+    assert_eq!(
+        listing_names(Config::builder(), listing),
+        ["a", "b", "c", "d"]
+    );
+    assert_eq!(
+        listing_names(Config::builder().dirsfirst(true), listing),
+        ["b", "c", "a", "d"]
+    );
+    assert_eq!(
+        listing_names(Config::builder().filesfirst(true), listing),
+        ["a", "d", "b", "c"]
+    );
+    // 分组与排序方式组合，-r只反转组内的顺序
+    assert_eq!(
+        listing_names(Config::builder().filesfirst(true).reverse(true), listing),
+        ["d", "a", "c", "b"]
+    );
+    assert_eq!(
+        listing_names(Config::builder().dirsfirst(true).unsorted(true), listing),
+        ["b", "c", "a", "d"]
+    );
+
+    assert!(
+        Config::builder()
+            .dirsfirst(true)
+            .filesfirst(true)
+            .build()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_name_collation_and_ignore_case() {
+    let listing = "Zeta\nalpha\nécole\nezra\nfile10\nfile2\nBeta\nreadme\nREADME\n";
+
+    // 重音字母排在对应的基本字母旁边
+    assert_eq!(
+        listing_names(Config::builder(), listing),
+        [
+            "Beta", "README", "Zeta", "alpha", "école", "ezra", "file10", "file2", "readme"
+        ]
+    );
+    // 忽略大小写，只有大小写不同的名称按原始字节排序
+    assert_eq!(
+        listing_names(Config::builder().ignore_case(true), listing),
+        [
+            "alpha", "Beta", "école", "ezra", "file10", "file2", "README", "readme", "Zeta"
+        ]
+    );
+    assert_eq!(
+        listing_names(
+            Config::builder().ignore_case(true).sort(SortKey::Version),
+            listing
+        ),
+        [
+            "alpha", "Beta", "école", "ezra", "file2", "file10", "README", "readme", "Zeta"
+        ]
+    );
+
+    // 没有分解形式的字母同样按基本字母排序
+    assert_eq!(
+        listing_names(Config::builder(), "zebra\nłza\nørn\nmango\nlemon\n"),
+        ["lemon", "łza", "mango", "ørn", "zebra"]
+    );
+
+    // --ignore-case同样作用于-I和-P
+    assert_eq!(
+        listing_names(
+            Config::builder().ignore_case(true).exclude("zeta|readme"),
+            listing
+        ),
+        ["alpha", "Beta", "école", "ezra", "file10", "file2"]
+    );
+    assert_eq!(
+        listing_names(Config::builder().exclude("zeta"), listing).len(),
+        9,
+        "默认区分大小写"
+    );
+}
+
+#[test]
+fn test_missing_metadata() {
+    use treecmd::{Entry, FileKind, TreeNode};
+
+    // 缺少大小或时间的条目总是排在最后，-r也不例外
+    let listing = "a\t5\t100\nb\nc\t9\t200\nd\n";
+    assert_eq!(
+        listing_names(Config::builder().sort(SortKey::Size), listing),
+        ["c", "a", "b", "d"]
+    );
+    assert_eq!(
+        listing_names(Config::builder().sort(SortKey::Size).reverse(true), listing),
+        ["a", "c", "d", "b"]
+    );
+    assert_eq!(
+        listing_names(
+            Config::builder().sort(SortKey::Mtime).reverse(true),
+            listing
+        ),
+        ["a", "c", "d", "b"]
+    );
+
+    // 无法读取元数据的条目照常显示，并带有标记
+    let tree = TreeNode::from_entries(vec![
+        Entry::new("r", 0, FileKind::Dir),
+        Entry::new("r/gone", 1, FileKind::File).with_metadata_error("No such file or directory"),
+    ])
+    .unwrap();
+    let config = Config::builder()
+        .size(true)
+        .mtime(true)
+        .perms(true)
+        .no_color(true)
+        .build()
+        .unwrap();
+    let mut formatter = Formatter::new(config);
+    let text = formatter.tree_to_string(&tree);
+    assert!(text.contains("gone [cannot read metadata]\n"));
+    let json = formatter.json_to_string(Some(&tree), &[]);
+    assert!(json.contains("No such file or directory"));
+}
+
+#[test]
+fn test_glob_patterns() {
+    let listing = "node_modules/x.js\nsrc/main.o\nsrc/main.rs\nsrc/lib.rs\nREADME.md\n";
+    let shown = |builder: treecmd::ConfigBuilder| -> Vec<String> {
+        let config = builder.path("x").build().unwrap();
+        let tree = Walker::new(config)
+            .walk_listing(listing.as_bytes())
+            .unwrap();
+        let mut paths = Vec::new();
+        tree.visit(&mut |entry, _| {
+            if let Ok(path) = entry.path().strip_prefix("x")
+                && !path.as_os_str().is_empty()
+            {
+                paths.push(path.display().to_string());
+            }
+        });
+        paths
+    };
+    let expected = ["README.md", "src", "src/lib.rs", "src/main.rs"];
+
+    // glob语法，用|分隔多个候选
+    assert_eq!(
+        shown(Config::builder().exclude("node_modules|*.o")),
+        expected
+    );
+    // 选项可以重复指定
+    assert_eq!(
+        shown(Config::builder().exclude("node_modules").exclude("*.o")),
+        expected
+    );
+    // --regex保留正则表达式语义
+    assert_eq!(
+        shown(
+            Config::builder()
+                .regex(true)
+                .exclude(r"\.o$")
+                .exclude("^node")
+        ),
+        expected
+    );
+    assert_eq!(
+        shown(Config::builder().include("*.rs")),
+        ["node_modules", "src", "src/lib.rs", "src/main.rs"]
+    );
+
+    // --match-path匹配相对路径，*不跨越目录
+    assert_eq!(
+        shown(Config::builder().exclude("src/*.rs").match_path(true)),
+        [
+            "README.md",
+            "node_modules",
+            "node_modules/x.js",
+            "src",
+            "src/main.o"
+        ]
+    );
+    assert_eq!(shown(Config::builder().exclude("src/*.rs")).len(), 7);
+    assert_eq!(
+        shown(Config::builder().exclude("**/*.js").match_path(true)),
+        [
+            "README.md",
+            "node_modules",
+            "src",
+            "src/lib.rs",
+            "src/main.o",
+            "src/main.rs"
+        ]
+    );
+
+    let config = Config::try_parse_from(["tree", "-I", "a", "-I", "b|c"]).unwrap();
+    assert_eq!(config.args.exclude, ["a", "b|c"]);
+}
+
+#[test]
+fn test_include_pattern_keeps_directories() {
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+    std::fs::create_dir(dir.path().join("doc")).unwrap();
+    std::fs::write(dir.path().join("src/a.rs"), "").unwrap();
+    std::fs::write(dir.path().join("src/b.txt"), "").unwrap();
+    std::fs::write(dir.path().join("src/nested/c.rs"), "").unwrap();
+    std::fs::write(dir.path().join("doc/x.md"), "").unwrap();
+    std::fs::write(dir.path().join("top.rs"), "").unwrap();
+
+    let expected: Vec<PathBuf> = [
+        "",
+        "doc",
+        "src",
+        "src/a.rs",
+        "src/nested",
+        "src/nested/c.rs",
+        "top.rs",
+    ]
+    .iter()
+    .map(|path| dir.path().join(path))
+    .map(|path| path.components().collect())
+    .collect();
+
+    // -P只过滤文件，串行流式遍历和并行遍历结果相同
+    for paths in walked_paths(Config::builder().path(dir.path()).include("*.rs")) {
+        assert_eq!(paths, expected);
+    }
+}
+
+#[test]
+fn test_output_temp_file_not_listed() {
+    use treecmd::AtomicFile;
+
+    let dir = temp_dir();
+    std::fs::write(dir.path().join("a"), "").unwrap();
+
+    // 与`tree -a -o out.txt`相同：输出的临时文件位于遍历的目录中
+    let file = AtomicFile::create(dir.path().join("out.txt")).unwrap();
+    let temp = file.temp_path().to_path_buf();
+    assert!(temp.exists());
+
+    let builder = Config::builder().path(dir.path()).all(true);
+    for paths in walked_paths_with(builder, |walker| walker.skip_path(&temp)) {
+        assert_eq!(paths, [dir.path().to_path_buf(), dir.path().join("a")]);
+    }
+    file.commit().unwrap();
+}